@@ -1,8 +1,10 @@
 use proc_macro2::Span;
 use quote::{quote, quote_spanned};
 use syn::{
-    parse2, parse_macro_input, parse_quote, Attribute, DeriveInput, Field, GenericParam, Generics,
-    Ident, LitInt, Meta, Type, Visibility,
+    parse2, parse_macro_input, parse_quote,
+    visit_mut::{self, VisitMut},
+    Attribute, DeriveInput, Field, GenericParam, Generics, Ident, Lifetime, LitInt, Meta, Type,
+    TypeParamBound, Visibility,
 };
 
 #[proc_macro_derive(ShaderData, attributes(shader))]
@@ -14,6 +16,12 @@ pub fn derive_shader_data(input: proc_macro::TokenStream) -> proc_macro::TokenSt
     let generics = add_trait_bounds(input.generics);
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
+    // the generated vars struct doesn't borrow anything, so it drops the lifetime parameters
+    // and refers to field types with 'static in their place
+    let mut lifetimes = StaticLifetimes(vec![]);
+    let vars_generics = strip_lifetimes(&generics, &mut lifetimes);
+    let (_, vars_ty_generics, _) = vars_generics.split_for_impl();
+
     let struct_data = match input.data {
         syn::Data::Struct(struct_data) => struct_data,
         _ => {
@@ -28,14 +36,14 @@ pub fn derive_shader_data(input: proc_macro::TokenStream) -> proc_macro::TokenSt
     let shader_vars_name = Ident::new(&format!("{}__ShaderVars", name), name.span());
     let (shader_vars, shader_collect, shader_write) = match struct_data.fields {
         syn::Fields::Named(fields) => {
-            let fields = ShaderField::extract(fields.named.into_iter());
+            let fields = ShaderField::extract(fields.named);
 
             let shader_vars_fields = fields
                 .iter()
                 .map(|x| {
                     let vis = &x.vis;
                     let ident = x.ident.as_ref().unwrap();
-                    let ty = x.ty_encoder.as_ref().unwrap_or(&x.ty);
+                    let ty = lifetimes.apply(x.ty_encoder.as_ref().unwrap_or(&x.ty));
                     quote! { #vis #ident: <#ty as picodraw::ShaderData>::ShaderVars }
                 })
                 .collect::<Vec<_>>();
@@ -65,7 +73,7 @@ pub fn derive_shader_data(input: proc_macro::TokenStream) -> proc_macro::TokenSt
 
             (
                 quote! {
-                    #vis struct #shader_vars_name #generics {
+                    #vis struct #shader_vars_name #vars_generics {
                         #(#shader_vars_fields),*
                     }
                 },
@@ -81,13 +89,13 @@ pub fn derive_shader_data(input: proc_macro::TokenStream) -> proc_macro::TokenSt
         }
 
         syn::Fields::Unnamed(fields) => {
-            let fields = ShaderField::extract(fields.unnamed.into_iter());
+            let fields = ShaderField::extract(fields.unnamed);
 
             let shader_vars_fields = fields
                 .iter()
                 .map(|x| {
                     let vis = &x.vis;
-                    let ty = x.ty_encoder.as_ref().unwrap_or(&x.ty);
+                    let ty = lifetimes.apply(x.ty_encoder.as_ref().unwrap_or(&x.ty));
                     quote! { #vis <#ty as picodraw::ShaderData>::ShaderVars }
                 })
                 .collect::<Vec<_>>();
@@ -115,7 +123,7 @@ pub fn derive_shader_data(input: proc_macro::TokenStream) -> proc_macro::TokenSt
 
             (
                 quote! {
-                    #vis struct #shader_vars_name #generics (#(#shader_vars_fields),*);
+                    #vis struct #shader_vars_name #vars_generics (#(#shader_vars_fields),*);
                 },
                 quote! {
                     #shader_vars_name (#(#shader_collect_fields),*)
@@ -139,7 +147,7 @@ pub fn derive_shader_data(input: proc_macro::TokenStream) -> proc_macro::TokenSt
         #shader_vars
 
         impl #impl_generics picodraw::ShaderData for #name #ty_generics #where_clause {
-            type ShaderVars = #shader_vars_name #ty_generics;
+            type ShaderVars = #shader_vars_name #vars_ty_generics;
             fn shader_vars(vars: &mut dyn picodraw::ShaderVars) -> Self::ShaderVars {
                 #shader_collect
             }
@@ -160,6 +168,64 @@ fn add_trait_bounds(mut generics: Generics) -> Generics {
     generics
 }
 
+fn strip_lifetimes(generics: &Generics, lifetimes: &mut StaticLifetimes) -> Generics {
+    let mut generics = generics.clone();
+    generics.where_clause = None;
+    generics.params = generics
+        .params
+        .into_iter()
+        .filter_map(|param| match param {
+            GenericParam::Lifetime(param) => {
+                lifetimes.0.push(param.lifetime);
+                None
+            }
+            GenericParam::Type(mut param) => {
+                param.bounds = param
+                    .bounds
+                    .into_iter()
+                    .filter(|bound| !matches!(bound, TypeParamBound::Lifetime(_)))
+                    .collect();
+                Some(GenericParam::Type(param))
+            }
+            param => Some(param),
+        })
+        .collect();
+    generics
+}
+
+struct StaticLifetimes(Vec<Lifetime>);
+
+impl StaticLifetimes {
+    fn apply(&mut self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        self.visit_type_mut(&mut ty);
+        ty
+    }
+}
+
+impl VisitMut for StaticLifetimes {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        // `&'a T` has the same vars as `T`, unwrapping it avoids requiring `T: 'static`
+        if let Type::Reference(reference) = ty {
+            if let Some(lifetime) = &reference.lifetime {
+                if self.0.contains(lifetime) {
+                    *ty = (*reference.elem).clone();
+                    return self.visit_type_mut(ty);
+                }
+            }
+        }
+
+        visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if self.0.contains(lifetime) {
+            *lifetime = Lifetime::new("'static", lifetime.span());
+        }
+        visit_mut::visit_lifetime_mut(self, lifetime);
+    }
+}
+
 struct ShaderField {
     vis: Visibility,
     index: usize,
@@ -170,7 +236,7 @@ struct ShaderField {
 
 enum ShaderAttribute {
     Ignore,
-    EncoderType(Type),
+    EncoderType(Box<Type>),
 }
 
 impl ShaderAttribute {
@@ -182,9 +248,10 @@ impl ShaderAttribute {
                     if meta.tokens.to_string() == "ignore" {
                         shader_attr = Some(ShaderAttribute::Ignore);
                     } else {
-                        shader_attr = Some(Self::EncoderType(parse2(meta.tokens).expect(
-                            "invalid shader attribute structure, should be #[shader(Type)]",
-                        )));
+                        shader_attr =
+                            Some(Self::EncoderType(Box::new(parse2(meta.tokens).expect(
+                                "invalid shader attribute structure, should be #[shader(Type)]",
+                            ))));
                     }
 
                     break;
@@ -208,7 +275,7 @@ impl ShaderField {
                         index,
                         ident: field.ident,
                         ty: field.ty,
-                        ty_encoder: Some(ty_encoder),
+                        ty_encoder: Some(*ty_encoder),
                     }),
                     None => Some(ShaderField {
                         vis: field.vis,
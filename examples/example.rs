@@ -39,11 +39,12 @@ impl App {
 
             let mut gl = OpenGl::new(
                 &|c| context.get_proc_address(c.to_str().unwrap()),
-                OpenGlConfig { srgb: true },
+                OpenGlConfig {
+                    srgb: true,
+                    ..Default::default()
+                },
             );
-            gl.render(0, 0, |mut x| {
-                x.register::<Circle>();
-            });
+            gl.register::<Circle>().unwrap();
 
             context.make_not_current();
 
@@ -14,23 +14,77 @@ pub trait ShaderVars {
     fn read_int32(&mut self) -> Int;
     fn read_uint8(&mut self) -> Int;
     fn read_uint16(&mut self) -> Int;
+    /// Values above `i32::MAX` wrap around to negative numbers, use the `*_unsigned` methods
+    /// of [`Int`] to treat them as unsigned.
     fn read_uint32(&mut self) -> Int;
     fn read_float(&mut self) -> Float;
     fn texture(&mut self, tex: Arc<dyn Fn() -> image::DynamicImage>) -> Texture;
     fn resolution(&mut self) -> Float2;
+
+    /// Reads a 16 bit unsigned normalized value as a float in `[0, 1]`.
+    fn read_unorm16(&mut self) -> Float {
+        Float::from(self.read_uint16()) / 65535.0
+    }
+
+    /// Reads a 16 bit signed normalized value as a float in `[-1, 1]`.
+    fn read_snorm16(&mut self) -> Float {
+        (Float::from(self.read_int16()) / 32767.0).max(-1.0)
+    }
 }
 
 pub trait ShaderDataWriter {
     fn resolution(&self) -> (f32, f32);
     fn write_float(&mut self, x: f32);
     fn write_int(&mut self, x: i32);
+
+    fn write_uint8(&mut self, x: u8) {
+        self.write_int(x as i32)
+    }
+
+    fn write_uint16(&mut self, x: u16) {
+        self.write_int(x as i32)
+    }
+
+    fn write_uint32(&mut self, x: u32) {
+        self.write_int(x as i32)
+    }
+
+    /// Writes each value as its own field. Prefer this over a loop of [`write_float`](Self::write_float)
+    /// calls for large payloads, writers can copy fields laid out back to back in one go.
+    fn write_floats(&mut self, xs: &[f32]) {
+        for x in xs {
+            self.write_float(*x);
+        }
+    }
+
+    /// Writes each value as its own field, see [`write_floats`](Self::write_floats).
+    fn write_ints(&mut self, xs: &[i32]) {
+        for x in xs {
+            self.write_int(*x);
+        }
+    }
+
+    /// Writes every byte as its own field, matching a [`ShaderVars::read_uint8`] per byte.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_uint8(*byte);
+        }
+    }
+
+    /// Writes a float in `[0, 1]` for [`ShaderVars::read_unorm16`], clamping values outside the range.
+    fn write_unorm16(&mut self, x: f32) {
+        self.write_int((x.clamp(0.0, 1.0) * 65535.0).round() as i32)
+    }
+
+    /// Writes a float in `[-1, 1]` for [`ShaderVars::read_snorm16`], clamping values outside the range.
+    fn write_snorm16(&mut self, x: f32) {
+        self.write_int((x.clamp(-1.0, 1.0) * 32767.0).round() as i32)
+    }
 }
 
 impl ShaderData for () {
     type ShaderVars = ();
-    fn shader_vars(_vars: &mut dyn ShaderVars) -> Self::ShaderVars {
-        ()
-    }
+    fn shader_vars(_vars: &mut dyn ShaderVars) -> Self::ShaderVars {}
     fn write(&self, _writer: &mut dyn ShaderDataWriter) {}
 }
 
@@ -50,7 +104,7 @@ impl ShaderData for u8 {
         vars.read_uint8()
     }
     fn write(&self, writer: &mut dyn ShaderDataWriter) {
-        writer.write_int(*self as i32)
+        writer.write_uint8(*self)
     }
 }
 
@@ -60,7 +114,7 @@ impl ShaderData for u16 {
         vars.read_uint16()
     }
     fn write(&self, writer: &mut dyn ShaderDataWriter) {
-        writer.write_int(*self as i32)
+        writer.write_uint16(*self)
     }
 }
 
@@ -70,7 +124,7 @@ impl ShaderData for u32 {
         vars.read_uint32()
     }
     fn write(&self, writer: &mut dyn ShaderDataWriter) {
-        writer.write_int(*self as i32)
+        writer.write_uint32(*self)
     }
 }
 
@@ -100,7 +154,7 @@ impl ShaderData for i32 {
         vars.read_int32()
     }
     fn write(&self, writer: &mut dyn ShaderDataWriter) {
-        writer.write_int(*self as i32)
+        writer.write_int(*self)
     }
 }
 
@@ -132,19 +186,19 @@ impl<const N: usize, T: ShaderData> ShaderData for [T; N] {
     }
 
     fn write(&self, writer: &mut dyn ShaderDataWriter) {
-        for i in 0..N {
-            self[i].write(writer);
+        for value in self {
+            value.write(writer);
         }
     }
 }
 
-impl<'a, T: ShaderData> ShaderData for &'a T {
+impl<T: ShaderData> ShaderData for &T {
     type ShaderVars = T::ShaderVars;
     fn shader_vars(vars: &mut dyn ShaderVars) -> Self::ShaderVars {
         T::shader_vars(vars)
     }
     fn write(&self, writer: &mut dyn ShaderDataWriter) {
-        T::write(&self, writer)
+        T::write(self, writer)
     }
 }
 
@@ -172,3 +226,286 @@ impl_tuple!(A, B, C);
 impl_tuple!(A, B, C, D);
 impl_tuple!(A, B, C, D, E);
 impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+impl_tuple!(A, B, C, D, E, F, G, H, I);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// Packs 32 boolean flags into a single field, read in the shader with [`BitFlagsVars::get`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BitFlags(pub u32);
+
+pub struct BitFlagsVars {
+    // stored as two 16 bit halves, so neither reads back negative
+    lo: Int,
+    hi: Int,
+}
+
+impl BitFlagsVars {
+    #[track_caller]
+    pub fn get(&self, bit: u32) -> Bool {
+        assert!(bit < 32, "bit index out of range: {}", bit);
+        let word = if bit < 16 { self.lo } else { self.hi };
+        ((word / (1 << (bit % 16))) % 2).eq(1)
+    }
+}
+
+impl ShaderData for BitFlags {
+    type ShaderVars = BitFlagsVars;
+    fn shader_vars(vars: &mut dyn ShaderVars) -> Self::ShaderVars {
+        BitFlagsVars {
+            lo: vars.read_uint16(),
+            hi: vars.read_uint16(),
+        }
+    }
+    fn write(&self, writer: &mut dyn ShaderDataWriter) {
+        writer.write_uint16(self.0 as u16);
+        writer.write_uint16((self.0 >> 16) as u16);
+    }
+}
+
+impl From<u32> for BitFlags {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+#[allow(unused_macros)]
+macro_rules! impl_vector {
+    ($type:ty, $vars:ident, $($field:ident),*) => {
+        impl ShaderData for $type {
+            type ShaderVars = $vars;
+            fn shader_vars(vars: &mut dyn ShaderVars) -> Self::ShaderVars {
+                $vars::new($({ let $field = vars.read_float(); $field }),*)
+            }
+            fn write(&self, writer: &mut dyn ShaderDataWriter) {
+                $(writer.write_float(self.$field);)*
+            }
+        }
+    };
+}
+
+#[cfg(feature = "glam")]
+impl_vector!(glam::Vec2, Float2, x, y);
+#[cfg(feature = "glam")]
+impl_vector!(glam::Vec3, Float3, x, y, z);
+#[cfg(feature = "glam")]
+impl_vector!(glam::Vec4, Float4, x, y, z, w);
+
+#[cfg(feature = "mint")]
+impl_vector!(mint::Vector2<f32>, Float2, x, y);
+#[cfg(feature = "mint")]
+impl_vector!(mint::Vector3<f32>, Float3, x, y, z);
+#[cfg(feature = "mint")]
+impl_vector!(mint::Vector4<f32>, Float4, x, y, z, w);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graph::{Op, OpAddr, ShaderGraph},
+        types::GlType,
+    };
+
+    // records the kind of every field read or written in order, and the values of written ints
+    #[derive(Default)]
+    struct Recorder(Vec<&'static str>, Vec<i32>);
+
+    impl ShaderVars for Recorder {
+        fn read_int8(&mut self) -> Int {
+            self.0.push("i8");
+            Int::input_raw(self.0.len())
+        }
+        fn read_int16(&mut self) -> Int {
+            self.0.push("i16");
+            Int::input_raw(self.0.len())
+        }
+        fn read_int32(&mut self) -> Int {
+            self.0.push("i32");
+            Int::input_raw(self.0.len())
+        }
+        fn read_uint8(&mut self) -> Int {
+            self.0.push("u8");
+            Int::input_raw(self.0.len())
+        }
+        fn read_uint16(&mut self) -> Int {
+            self.0.push("u16");
+            Int::input_raw(self.0.len())
+        }
+        fn read_uint32(&mut self) -> Int {
+            self.0.push("u32");
+            Int::input_raw(self.0.len())
+        }
+        fn read_float(&mut self) -> Float {
+            self.0.push("f32");
+            Float::input_raw(self.0.len())
+        }
+        fn texture(&mut self, _tex: Arc<dyn Fn() -> image::DynamicImage>) -> Texture {
+            unimplemented!()
+        }
+        fn resolution(&mut self) -> Float2 {
+            unimplemented!()
+        }
+    }
+
+    impl ShaderDataWriter for Recorder {
+        fn resolution(&self) -> (f32, f32) {
+            (1.0, 1.0)
+        }
+        fn write_float(&mut self, _x: f32) {
+            self.0.push("f32");
+        }
+        fn write_int(&mut self, x: i32) {
+            self.0.push("int");
+            self.1.push(x);
+        }
+    }
+
+    fn reads<T: ShaderData>() -> Vec<&'static str> {
+        let mut vars = Recorder::default();
+        ShaderGraph::collect(|| {
+            T::shader_vars(&mut vars);
+            Float::from(0.0)
+        })
+        .unwrap();
+        vars.0
+    }
+
+    fn writes<T: ShaderData>(data: &T) -> Vec<&'static str> {
+        let mut writer = Recorder::default();
+        data.write(&mut writer);
+        writer.0
+    }
+
+    #[test]
+    fn tuple_fields_in_order() {
+        type Tuple = (u8, f32, i32, u16, f32, bool, i8, u32, f32, i16, f32, u8);
+        let data: Tuple = (1, 2.0, 3, 4, 5.0, true, 7, 8, 9.0, 10, 11.0, 12);
+
+        assert_eq!(
+            reads::<Tuple>(),
+            ["u8", "f32", "i32", "u16", "f32", "u8", "i8", "u32", "f32", "i16", "f32", "u8"]
+        );
+        assert_eq!(
+            writes(&data),
+            ["int", "f32", "int", "int", "f32", "int", "int", "int", "f32", "int", "f32", "int"]
+        );
+    }
+
+    #[derive(ShaderData)]
+    struct Borrowed<'a> {
+        a: &'a f32,
+        b: u8,
+    }
+
+    #[derive(ShaderData)]
+    struct Nested<'a, 'b> {
+        inner: Borrowed<'a>,
+        tail: &'b (i16, f32),
+    }
+
+    #[test]
+    fn derive_strips_lifetimes() {
+        let a = 1.0;
+        let tail = (2, 3.0);
+        let data = Nested {
+            inner: Borrowed { a: &a, b: 4 },
+            tail: &tail,
+        };
+
+        assert_eq!(reads::<Nested>(), ["f32", "u8", "i16", "f32"]);
+        assert_eq!(writes(&data), ["f32", "int", "int", "f32"]);
+    }
+
+    #[test]
+    fn unsigned_writers_round_trip() {
+        let mut writer = Recorder::default();
+        writer.write_uint8(u8::MAX);
+        writer.write_uint16(u16::MAX);
+        writer.write_uint32(u32::MAX);
+        writer.write_bytes(&[0, 7, u8::MAX]);
+
+        // the encoder truncates each int to the width of its field
+        let ints = &writer.1;
+        assert_eq!(ints[0] as u8, u8::MAX);
+        assert_eq!(ints[1] as u16, u16::MAX);
+        assert_eq!(ints[2] as u32, u32::MAX);
+        assert_eq!(
+            ints[3..].iter().map(|x| *x as u8).collect::<Vec<_>>(),
+            [0, 7, u8::MAX]
+        );
+    }
+
+    #[test]
+    fn normalized_writers_round_trip() {
+        let mut writer = Recorder::default();
+        for x in [0.0, 0.25, 1.0 / 3.0, 1.0, 2.0] {
+            writer.write_unorm16(x);
+            let read = writer.1.pop().unwrap() as u16 as f32 / 65535.0;
+            assert!(
+                (read - x.min(1.0)).abs() <= 0.5 / 65535.0,
+                "{} -> {}",
+                x,
+                read
+            );
+        }
+
+        for x in [-2.0, -1.0, -0.5, 0.0, 1.0 / 3.0, 1.0] {
+            writer.write_snorm16(x);
+            let read = (writer.1.pop().unwrap() as i16 as f32 / 32767.0).max(-1.0);
+            assert!(
+                (read - x.max(-1.0)).abs() <= 0.5 / 32767.0,
+                "{} -> {}",
+                x,
+                read
+            );
+        }
+    }
+
+    // evaluates the int ops a graph is made of, the recorder numbers its inputs from 1
+    fn eval(graph: &ShaderGraph<Bool>, inputs: &[i32]) -> bool {
+        let mut values = vec![];
+        for (_, op, _) in graph.iter() {
+            let value = |addr: OpAddr| values[addr.id() as usize];
+            let value = match op {
+                Op::Input(id) => inputs[id - 1],
+                Op::LitInt(x) => x,
+                Op::Div(a, b) => value(a) / value(b),
+                Op::Rem(a, b) => value(a) % value(b),
+                Op::Eq(a, b) => (value(a) == value(b)) as i32,
+                op => panic!("unexpected op {:?}", op),
+            };
+            values.push(value);
+        }
+        values[graph.result().id() as usize] != 0
+    }
+
+    #[test]
+    fn bitflags() {
+        let flags = BitFlags(0x8001_00b5);
+        assert_eq!(reads::<BitFlags>(), ["u16", "u16"]);
+
+        let mut writer = Recorder::default();
+        flags.write(&mut writer);
+        let inputs = writer
+            .1
+            .iter()
+            .map(|x| *x as u16 as i32)
+            .collect::<Vec<_>>();
+        assert_eq!(inputs, [0x00b5, 0x8001]);
+
+        for bit in (0..8).chain([15, 16, 17, 31]) {
+            let graph =
+                ShaderGraph::collect(|| BitFlags::shader_vars(&mut Recorder::default()).get(bit))
+                    .unwrap();
+            assert_eq!(
+                eval(&graph, &inputs),
+                flags.0 >> bit & 1 == 1,
+                "bit {}",
+                bit
+            );
+        }
+    }
+}
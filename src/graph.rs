@@ -1,41 +1,308 @@
 use crate::types::GlType;
 use std::cell::RefCell;
-use std::fmt::Debug;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
 use std::marker::PhantomData;
-use std::mem::replace;
+use std::panic::Location;
 
 thread_local! {
-    static CURRENT_GRAPH: RefCell<Option<Vec<(Op, ValueType)>>> = RefCell::new(None);
+    static CURRENT_GRAPH: RefCell<Option<GraphBuilder>> = const { RefCell::new(None) };
 }
 
+#[derive(Default)]
+struct GraphBuilder {
+    values: Vec<(Op, ValueType)>,
+    #[cfg(feature = "debug-graph")]
+    locations: Vec<&'static Location<'static>>,
+}
+
+#[track_caller]
 pub(crate) fn push_op(value: Op, r#type: ValueType) -> OpAddr {
+    #[cfg(feature = "debug-graph")]
+    let location = Location::caller();
+
     CURRENT_GRAPH.with(|graph| {
         let mut graph = graph.borrow_mut();
-        let graph = graph
-            .as_mut()
-            .expect("executing not in a shader graph context");
+        let graph = graph.as_mut().expect(
+            "shader values can only be created inside a shader graph context (Shader::draw)",
+        );
 
-        graph.push((value, r#type));
-        OpAddr((graph.len() - 1) as u32, PhantomData)
+        graph.values.push((value, r#type));
+        #[cfg(feature = "debug-graph")]
+        graph.locations.push(location);
+        OpAddr((graph.values.len() - 1) as u32, PhantomData)
     })
 }
 
+// restores the outer graph context even if the collecting closure panics
+struct CollectGuard(Option<GraphBuilder>);
+
+impl Drop for CollectGuard {
+    fn drop(&mut self) {
+        CURRENT_GRAPH.with(|engine| *engine.borrow_mut() = self.0.take());
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ShaderGraph<T> {
     values: Vec<(Op, ValueType)>,
+    /// Where each op was created, indexed like `values`.
+    #[cfg(feature = "debug-graph")]
+    locations: Vec<&'static Location<'static>>,
     result: T,
 }
 
+/// Ops are identified by their index in the graph. `location` is the source location that created
+/// the failing op, only captured with the `debug-graph` feature.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphError {
+    /// An op got operands of types it doesn't accept, `op` is its debug representation.
+    TypeCheck {
+        index: u32,
+        op: String,
+        location: Option<&'static Location<'static>>,
+    },
+    /// An op depends on a value that isn't defined before it.
+    ForwardReference {
+        index: u32,
+        dependency: u32,
+        location: Option<&'static Location<'static>>,
+    },
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = match self {
+            GraphError::TypeCheck {
+                index,
+                op,
+                location,
+            } => {
+                write!(f, "shader graph type check failed at ${:x} ({})", index, op)?;
+                location
+            }
+            GraphError::ForwardReference {
+                index,
+                dependency,
+                location,
+            } => {
+                write!(
+                    f,
+                    "shader graph op at ${:x} references ${:x}, which is not defined before it",
+                    index, dependency
+                )?;
+                location
+            }
+        };
+
+        match location {
+            Some(location) => write!(f, ", created at {}", location),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+// structural equality (same ops in the same order, same result), used by the shader map to share
+// ids between identical shaders
+impl<T: GlType> PartialEq for ShaderGraph<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.result() == other.result() && self.values == other.values
+    }
+}
+
 impl<T: GlType> ShaderGraph<T> {
-    pub fn collect(c: impl FnOnce() -> T) -> Self {
-        let prev = CURRENT_GRAPH.with(|engine| replace(&mut *engine.borrow_mut(), Some(vec![])));
+    pub fn collect(c: impl FnOnce() -> T) -> Result<Self, GraphError> {
+        let guard = CollectGuard(
+            CURRENT_GRAPH.with(|engine| engine.borrow_mut().replace(GraphBuilder::default())),
+        );
         let result = c();
-        let values = CURRENT_GRAPH.with(|engine| replace(&mut *engine.borrow_mut(), prev));
+        let builder = CURRENT_GRAPH
+            .with(|engine| engine.borrow_mut().take())
+            .unwrap();
+        drop(guard);
 
-        Self {
-            values: values.unwrap(),
+        let mut graph = Self {
+            values: builder.values,
+            #[cfg(feature = "debug-graph")]
+            locations: builder.locations,
             result,
+        };
+
+        graph.type_check()?;
+        graph.fold_constants();
+        Ok(graph)
+    }
+
+    // folds boolean ops over literals, and selects on a literal condition, so every backend gets them
+    // for free. folded ops stay in place, selects are only unlinked and become dead
+    fn fold_constants(&mut self) {
+        let mut remap = (0..self.values.len() as u32)
+            .map(|id| OpAddr(id, PhantomData))
+            .collect::<Vec<_>>();
+
+        for id in 0..self.values.len() {
+            let mut op = self.values[id].0;
+            op.visit_dependencies_mut(|dep| *dep = remap[dep.0 as usize]);
+
+            let values = &self.values;
+            let boolean = |addr: OpAddr| match values[addr.0 as usize].0 {
+                Op::LitBool(x) => Some(x),
+                _ => None,
+            };
+
+            // `None` unless both sides are literals, `Some(None)` for unordered floats
+            let compare =
+                |a: OpAddr, b: OpAddr| match (values[a.0 as usize].0, values[b.0 as usize].0) {
+                    (Op::LitFloat(a), Op::LitFloat(b)) => Some(a.partial_cmp(&b)),
+                    (Op::LitInt(a), Op::LitInt(b)) => Some(Some(a.cmp(&b))),
+                    _ => None,
+                };
+
+            let folded = match op {
+                Op::Select(c, a, b) => {
+                    // slots can be updated between the select and its users, so only plain values are
+                    // safe to read in place of the select
+                    let value = |addr: OpAddr| {
+                        !matches!(
+                            values[addr.0 as usize].0,
+                            Op::SlotCreate(_)
+                                | Op::SlotUpdate(_, _)
+                                | Op::LoopPush(_)
+                                | Op::LoopPop
+                        )
+                    };
+
+                    match boolean(c) {
+                        Some(true) if value(a) => remap[id] = a,
+                        Some(false) if value(b) => remap[id] = b,
+                        _ => {}
+                    }
+
+                    None
+                }
+
+                Op::Not(a) => boolean(a).map(|a| !a),
+                Op::And(a, b) => boolean(a).zip(boolean(b)).map(|(a, b)| a && b),
+                Op::Or(a, b) => boolean(a).zip(boolean(b)).map(|(a, b)| a || b),
+                Op::Xor(a, b) => boolean(a).zip(boolean(b)).map(|(a, b)| a != b),
+
+                // unordered (nan) comparisons are only true for Ne, same as in glsl
+                Op::Eq(a, b) => compare(a, b).map(|x| x.is_some_and(|x| x.is_eq())),
+                Op::Ne(a, b) => compare(a, b).map(|x| !x.is_some_and(|x| x.is_eq())),
+                Op::Lt(a, b) => compare(a, b).map(|x| x.is_some_and(|x| x.is_lt())),
+                Op::Le(a, b) => compare(a, b).map(|x| x.is_some_and(|x| x.is_le())),
+                Op::Gt(a, b) => compare(a, b).map(|x| x.is_some_and(|x| x.is_gt())),
+                Op::Ge(a, b) => compare(a, b).map(|x| x.is_some_and(|x| x.is_ge())),
+
+                _ => None,
+            };
+
+            self.values[id].0 = match folded {
+                Some(x) => Op::LitBool(x),
+                None => op,
+            };
+        }
+
+        self.result = T::wrap(remap[self.result.unwrap().0 as usize]);
+        debug_assert!(
+            self.type_check().is_ok(),
+            "constant folding broke the graph"
+        );
+    }
+
+    fn type_check(&self) -> Result<(), GraphError> {
+        for (id, (op, ty)) in self.values.iter().enumerate() {
+            let addr = OpAddr(id as u32, PhantomData);
+
+            let mut forward = None;
+            op.visit_dependencies(|dep| {
+                if dep.0 >= addr.0 && forward.is_none() {
+                    forward = Some(dep);
+                }
+            });
+
+            if let Some(dep) = forward {
+                return Err(GraphError::ForwardReference {
+                    index: addr.0,
+                    dependency: dep.0,
+                    location: self.location(addr),
+                });
+            }
+
+            if !op.type_check(*ty, |dep| {
+                self.values.get(dep.0 as usize).map(|(_, ty)| *ty)
+            }) {
+                return Err(GraphError::TypeCheck {
+                    index: addr.0,
+                    op: format!("{:?}", op),
+                    location: self.location(addr),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // hash of the op stream that doesn't change across runs, platforms or compiler versions,
+    // used by the shader map to find structurally identical shaders
+    pub(crate) fn stable_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        for (op, ty) in &self.values {
+            op.stable_hash(&mut hasher);
+            hasher.write_u8(*ty as u8);
         }
+        hasher.write_u32(self.result.unwrap().0);
+        hasher.finish()
+    }
+
+    // graphviz DOT rendering with edges from dependencies to their users,
+    // exposed through OpenGl::shader_graph_dot
+    pub(crate) fn to_dot(&self) -> String {
+        let mut out = String::from("digraph shader {\n  node [shape=box, style=filled];\n");
+        for (id, (op, ty)) in self.values.iter().enumerate() {
+            let color = match ty {
+                ty if ty.is_float() => "lightblue",
+                ty if ty.is_int() => "palegreen",
+                ty if ty.is_bool() => "khaki",
+                _ => "plum",
+            };
+
+            let label = format!("{:?}: {:?}", op, ty).replace('"', "\\\"");
+            out.push_str(&format!(
+                "  n{} [label=\"{}\", fillcolor={}];\n",
+                id, label, color
+            ));
+
+            op.visit_dependencies(|dep| out.push_str(&format!("  n{} -> n{};\n", dep.0, id)));
+        }
+
+        out.push_str(&format!(
+            "  n{} [penwidth=3];\n}}\n",
+            self.result.unwrap().0
+        ));
+        out
+    }
+
+    // counts how many live ops use each value. values that don't contribute to the result
+    // (directly or through loop slots) are missing from the map
+    pub(crate) fn usages(&self) -> HashMap<OpAddr, u32> {
+        let mut usages = HashMap::<OpAddr, u32>::new();
+        *usages.entry(self.result()).or_default() += 1;
+        for (id, op, _) in self.iter().rev() {
+            if matches!(op, Op::SlotUpdate(_, _) | Op::LoopPush(_) | Op::LoopPop) {
+                *usages.entry(id).or_default() += 1;
+            }
+
+            if usages.contains_key(&id) {
+                op.visit_dependencies(|dep| {
+                    *usages.entry(dep).or_default() += 1;
+                });
+            }
+        }
+        usages
     }
 
     pub fn get(&self, value: OpAddr) -> (&Op, ValueType) {
@@ -45,8 +312,7 @@ impl<T: GlType> ShaderGraph<T> {
 
     pub fn iter<'a>(
         &'a self,
-    ) -> impl Iterator<Item = (OpAddr, Op, ValueType)> + DoubleEndedIterator + ExactSizeIterator + 'a
-    {
+    ) -> impl DoubleEndedIterator<Item = (OpAddr, Op, ValueType)> + ExactSizeIterator + 'a {
         self.values
             .iter()
             .enumerate()
@@ -56,6 +322,18 @@ impl<T: GlType> ShaderGraph<T> {
     pub fn result(&self) -> OpAddr {
         self.result.unwrap()
     }
+
+    /// Source location that created the op. Always `None` without the `debug-graph` feature.
+    pub fn location(&self, value: OpAddr) -> Option<&'static Location<'static>> {
+        #[cfg(feature = "debug-graph")]
+        return self.locations.get(value.0 as usize).copied();
+
+        #[cfg(not(feature = "debug-graph"))]
+        {
+            let _ = value;
+            None
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
@@ -166,7 +444,185 @@ pub enum Op {
 }
 
 impl Op {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let opcode: u8 = match self {
+            Op::Input(x) => {
+                hasher.write_u64(*x as u64);
+                0
+            }
+            Op::Add(_, _) => 1,
+            Op::Sub(_, _) => 2,
+            Op::Mul(_, _) => 3,
+            Op::Div(_, _) => 4,
+            Op::Rem(_, _) => 5,
+            Op::Dot(_, _) => 6,
+            Op::Cross(_, _) => 7,
+            Op::Neg(_) => 8,
+            Op::Sin(_) => 9,
+            Op::Cos(_) => 10,
+            Op::Tan(_) => 11,
+            Op::Asin(_) => 12,
+            Op::Acos(_) => 13,
+            Op::Atan(_) => 14,
+            Op::Atan2(_, _) => 15,
+            Op::Sqrt(_) => 16,
+            Op::Pow(_, _) => 17,
+            Op::Exp(_) => 18,
+            Op::Ln(_) => 19,
+            Op::Min(_, _) => 20,
+            Op::Max(_, _) => 21,
+            Op::Clamp(_, _, _) => 22,
+            Op::Abs(_) => 23,
+            Op::Sign(_) => 24,
+            Op::Floor(_) => 25,
+            Op::Fract(_) => 26,
+            Op::Lerp(_, _, _) => 27,
+            Op::Smoothstep(_, _, _) => 28,
+            Op::Step(_, _) => 29,
+            Op::Select(_, _, _) => 30,
+            Op::LitFloat(x) => {
+                hasher.write_u32(x.to_bits());
+                31
+            }
+            Op::LitInt(x) => {
+                hasher.write_u32(*x as u32);
+                32
+            }
+            Op::LitBool(x) => {
+                hasher.write_u8(*x as u8);
+                33
+            }
+            Op::Eq(_, _) => 34,
+            Op::Ne(_, _) => 35,
+            Op::Lt(_, _) => 36,
+            Op::Le(_, _) => 37,
+            Op::Gt(_, _) => 38,
+            Op::Ge(_, _) => 39,
+            Op::And(_, _) => 40,
+            Op::Or(_, _) => 41,
+            Op::Xor(_, _) => 42,
+            Op::Not(_) => 43,
+            Op::NewVec2(_, _) => 44,
+            Op::NewVec3(_, _, _) => 45,
+            Op::NewVec4(_, _, _, _) => 46,
+            Op::SplatVec2(_) => 47,
+            Op::SplatVec3(_) => 48,
+            Op::SplatVec4(_) => 49,
+            Op::CastFloat(_) => 50,
+            Op::CastInt(_) => 51,
+            Op::Swizzle1(_, swizzle) => {
+                hasher.write_u8(*swizzle as u8);
+                52
+            }
+            Op::Length(_) => 53,
+            Op::Normalize(_) => 54,
+            Op::DerivX(_) => 55,
+            Op::DerivY(_) => 56,
+            Op::DerivWidth(_) => 57,
+            Op::TextureSampleLinear(_, _) => 58,
+            Op::TextureSampleNearest(_, _) => 59,
+            Op::TextureSize(_) => 60,
+            Op::SlotCreate(_) => 61,
+            Op::SlotUpdate(_, _) => 62,
+            Op::LoopPush(_) => 63,
+            Op::LoopPop => 64,
+        };
+
+        hasher.write_u8(opcode);
+        self.visit_dependencies(|dep| hasher.write_u32(dep.0));
+    }
+
+    fn type_check(&self, ty: ValueType, dep: impl Fn(OpAddr) -> Option<ValueType>) -> bool {
+        use ValueType::*;
+
+        let same = |a: &OpAddr| dep(*a) == Some(ty);
+        let is = |a: &OpAddr, expected: ValueType| dep(*a) == Some(expected);
+
+        match self {
+            Op::Input(_) => true,
+            Op::LitFloat(_) => ty == Float1,
+            Op::LitInt(_) => ty == Int1,
+            Op::LitBool(_) => ty == Bool1,
+
+            Op::Add(a, b) | Op::Sub(a, b) | Op::Mul(a, b) | Op::Div(a, b) | Op::Rem(a, b) => {
+                (ty.is_float() || ty.is_int()) && same(a) && same(b)
+            }
+            Op::Min(a, b) | Op::Max(a, b) => (ty.is_float() || ty.is_int()) && same(a) && same(b),
+            Op::Clamp(a, b, c) => (ty.is_float() || ty.is_int()) && same(a) && same(b) && same(c),
+            Op::Neg(a) | Op::Abs(a) | Op::Sign(a) => (ty.is_float() || ty.is_int()) && same(a),
+
+            Op::Sin(a)
+            | Op::Cos(a)
+            | Op::Tan(a)
+            | Op::Asin(a)
+            | Op::Acos(a)
+            | Op::Atan(a)
+            | Op::Sqrt(a)
+            | Op::Exp(a)
+            | Op::Ln(a)
+            | Op::Floor(a)
+            | Op::Fract(a)
+            | Op::DerivX(a)
+            | Op::DerivY(a)
+            | Op::DerivWidth(a) => ty.is_float() && same(a),
+            Op::Normalize(a) => ty.is_float() && ty.size() > 1 && same(a),
+            Op::Atan2(a, b) | Op::Pow(a, b) | Op::Step(a, b) => ty.is_float() && same(a) && same(b),
+            Op::Lerp(a, b, c) | Op::Smoothstep(a, b, c) => {
+                ty.is_float() && same(a) && same(b) && same(c)
+            }
+
+            Op::Dot(a, b) => {
+                ty == Float1 && dep(*a).is_some_and(|x| x.is_float()) && dep(*a) == dep(*b)
+            }
+            Op::Length(a) => ty == Float1 && dep(*a).is_some_and(|x| x.is_float()),
+            Op::Cross(a, b) => ty == Float3 && same(a) && same(b),
+            Op::Select(c, a, b) => is(c, Bool1) && same(a) && same(b),
+
+            Op::Eq(a, b)
+            | Op::Ne(a, b)
+            | Op::Lt(a, b)
+            | Op::Le(a, b)
+            | Op::Gt(a, b)
+            | Op::Ge(a, b) => {
+                ty == Bool1 && dep(*a).is_some_and(|x| x.size() == 1) && dep(*a) == dep(*b)
+            }
+            Op::And(a, b) | Op::Or(a, b) | Op::Xor(a, b) => ty.is_bool() && same(a) && same(b),
+            Op::Not(a) => ty.is_bool() && same(a),
+
+            Op::NewVec2(a, b) => ty == Float2 && is(a, Float1) && is(b, Float1),
+            Op::NewVec3(a, b, c) => ty == Float3 && is(a, Float1) && is(b, Float1) && is(c, Float1),
+            Op::NewVec4(a, b, c, d) => {
+                ty == Float4 && is(a, Float1) && is(b, Float1) && is(c, Float1) && is(d, Float1)
+            }
+            Op::SplatVec2(a) => ty == Float2 && is(a, Float1),
+            Op::SplatVec3(a) => ty == Float3 && is(a, Float1),
+            Op::SplatVec4(a) => ty == Float4 && is(a, Float1),
+
+            Op::CastFloat(a) => ty == Float1 && dep(*a).is_some_and(|x| x.size() == 1),
+            Op::CastInt(a) => ty == Int1 && dep(*a).is_some_and(|x| x.size() == 1),
+
+            Op::Swizzle1(a, swizzle) => dep(*a).is_some_and(|x| {
+                x.size() > *swizzle as usize && x.size() > 1 && x.scalar() == Some(ty)
+            }),
+
+            Op::TextureSampleLinear(a, b) | Op::TextureSampleNearest(a, b) => {
+                ty == Float4 && is(a, Texture) && is(b, Float2)
+            }
+            Op::TextureSize(a) => ty == Float2 && is(a, Texture),
+
+            Op::SlotCreate(a) => same(a),
+            Op::SlotUpdate(a, b) => same(a) && same(b),
+            Op::LoopPush(a) => is(a, Bool1),
+            Op::LoopPop => true,
+        }
+    }
+
     pub fn visit_dependencies(&self, mut v: impl FnMut(OpAddr)) {
+        let mut op = *self;
+        op.visit_dependencies_mut(|dep| v(*dep));
+    }
+
+    pub fn visit_dependencies_mut(&mut self, mut v: impl FnMut(&mut OpAddr)) {
         match self {
             Op::Input(_) => {}
 
@@ -174,177 +630,178 @@ impl Op {
             Op::LitInt(_) => {}
             Op::LitBool(_) => {}
             Op::Add(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Sub(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Mul(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Div(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Rem(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Dot(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Cross(a, b) => {
-                v(*a);
-                v(*b);
-            }
-            Op::Neg(a) => v(*a),
-            Op::Sin(a) => v(*a),
-            Op::Cos(a) => v(*a),
-            Op::Tan(a) => v(*a),
-            Op::Asin(a) => v(*a),
-            Op::Acos(a) => v(*a),
-            Op::Atan(a) => v(*a),
+                v(a);
+                v(b);
+            }
+            Op::Neg(a) => v(a),
+            Op::Sin(a) => v(a),
+            Op::Cos(a) => v(a),
+            Op::Tan(a) => v(a),
+            Op::Asin(a) => v(a),
+            Op::Acos(a) => v(a),
+            Op::Atan(a) => v(a),
             Op::Atan2(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
-            Op::Sqrt(a) => v(*a),
+            Op::Sqrt(a) => v(a),
             Op::Pow(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
-            Op::Exp(a) => v(*a),
-            Op::Ln(a) => v(*a),
+            Op::Exp(a) => v(a),
+            Op::Ln(a) => v(a),
             Op::Min(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Max(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Clamp(a, b, c) => {
-                v(*a);
-                v(*b);
-                v(*c);
-            }
-            Op::Abs(a) => v(*a),
-            Op::Sign(a) => v(*a),
-            Op::Floor(a) => v(*a),
-            Op::Fract(a) => v(*a),
+                v(a);
+                v(b);
+                v(c);
+            }
+            Op::Abs(a) => v(a),
+            Op::Sign(a) => v(a),
+            Op::Floor(a) => v(a),
+            Op::Fract(a) => v(a),
             Op::Select(a, b, c) => {
-                v(*a);
-                v(*b);
-                v(*c);
+                v(a);
+                v(b);
+                v(c);
             }
             Op::Lerp(a, b, c) => {
-                v(*a);
-                v(*b);
-                v(*c);
+                v(a);
+                v(b);
+                v(c);
             }
             Op::Smoothstep(a, b, c) => {
-                v(*a);
-                v(*b);
-                v(*c);
+                v(a);
+                v(b);
+                v(c);
             }
             Op::Step(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Eq(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Ne(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Lt(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Le(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Gt(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Ge(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::And(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Or(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::Xor(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
-            Op::Not(a) => v(*a),
+            Op::Not(a) => v(a),
             Op::NewVec2(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::NewVec3(a, b, c) => {
-                v(*a);
-                v(*b);
-                v(*c);
+                v(a);
+                v(b);
+                v(c);
             }
             Op::NewVec4(a, b, c, d) => {
-                v(*a);
-                v(*b);
-                v(*c);
-                v(*d);
-            }
-            Op::SplatVec2(a) => v(*a),
-            Op::SplatVec3(a) => v(*a),
-            Op::SplatVec4(a) => v(*a),
-            Op::CastFloat(a) => v(*a),
-            Op::CastInt(a) => v(*a),
-            Op::Length(a) => v(*a),
-            Op::Normalize(a) => v(*a),
-            Op::Swizzle1(a, _) => v(*a),
-            Op::DerivX(a) => v(*a),
-            Op::DerivY(a) => v(*a),
-            Op::DerivWidth(a) => v(*a),
+                v(a);
+                v(b);
+                v(c);
+                v(d);
+            }
+            Op::SplatVec2(a) => v(a),
+            Op::SplatVec3(a) => v(a),
+            Op::SplatVec4(a) => v(a),
+            Op::CastFloat(a) => v(a),
+            Op::CastInt(a) => v(a),
+            Op::Length(a) => v(a),
+            Op::Normalize(a) => v(a),
+            Op::Swizzle1(a, _) => v(a),
+            Op::DerivX(a) => v(a),
+            Op::DerivY(a) => v(a),
+            Op::DerivWidth(a) => v(a),
 
             Op::TextureSampleLinear(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::TextureSampleNearest(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::TextureSize(a) => {
-                v(*a);
+                v(a);
             }
             Op::SlotCreate(a) => {
-                v(*a);
+                v(a);
             }
             Op::SlotUpdate(a, b) => {
-                v(*a);
-                v(*b);
+                v(a);
+                v(b);
             }
             Op::LoopPush(a) => {
-                v(*a);
+                v(a);
             }
             Op::LoopPop => {}
         }
     }
 }
 
+/// The type of a value in a shader graph.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ValueType {
     Float1,
@@ -365,6 +822,122 @@ pub enum ValueType {
     Texture,
 }
 
+impl ValueType {
+    /// Number of vector lanes, textures count as 1.
+    ///
+    /// ```
+    /// use picodraw::ValueType;
+    /// assert_eq!(ValueType::Float3.size(), 3);
+    /// assert_eq!(ValueType::Bool1.size(), 1);
+    /// assert_eq!(ValueType::Texture.size(), 1);
+    /// ```
+    pub fn size(&self) -> usize {
+        match self {
+            ValueType::Float1 | ValueType::Int1 | ValueType::Bool1 | ValueType::Texture => 1,
+            ValueType::Float2 | ValueType::Int2 | ValueType::Bool2 => 2,
+            ValueType::Float3 | ValueType::Int3 | ValueType::Bool3 => 3,
+            ValueType::Float4 | ValueType::Int4 | ValueType::Bool4 => 4,
+        }
+    }
+
+    /// ```
+    /// use picodraw::ValueType;
+    /// assert!(ValueType::Float2.is_float());
+    /// assert!(!ValueType::Int2.is_float());
+    /// ```
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            ValueType::Float1 | ValueType::Float2 | ValueType::Float3 | ValueType::Float4
+        )
+    }
+
+    /// ```
+    /// use picodraw::ValueType;
+    /// assert!(ValueType::Int1.is_int());
+    /// assert!(!ValueType::Float1.is_int());
+    /// ```
+    pub fn is_int(&self) -> bool {
+        matches!(
+            self,
+            ValueType::Int1 | ValueType::Int2 | ValueType::Int3 | ValueType::Int4
+        )
+    }
+
+    /// ```
+    /// use picodraw::ValueType;
+    /// assert!(ValueType::Bool4.is_bool());
+    /// assert!(!ValueType::Int4.is_bool());
+    /// ```
+    pub fn is_bool(&self) -> bool {
+        matches!(
+            self,
+            ValueType::Bool1 | ValueType::Bool2 | ValueType::Bool3 | ValueType::Bool4
+        )
+    }
+
+    /// ```
+    /// use picodraw::ValueType;
+    /// assert!(ValueType::Texture.is_texture());
+    /// assert!(!ValueType::Float4.is_texture());
+    /// ```
+    pub fn is_texture(&self) -> bool {
+        matches!(self, ValueType::Texture)
+    }
+
+    /// The single lane type of a vector (or scalar) type, `None` for textures.
+    ///
+    /// ```
+    /// use picodraw::ValueType;
+    /// assert_eq!(ValueType::Int3.scalar(), Some(ValueType::Int1));
+    /// assert_eq!(ValueType::Float1.scalar(), Some(ValueType::Float1));
+    /// assert_eq!(ValueType::Texture.scalar(), None);
+    /// ```
+    pub fn scalar(&self) -> Option<ValueType> {
+        if self.is_float() {
+            Some(ValueType::Float1)
+        } else if self.is_int() {
+            Some(ValueType::Int1)
+        } else if self.is_bool() {
+            Some(ValueType::Bool1)
+        } else {
+            None
+        }
+    }
+}
+
+// 64-bit FNV-1a, all integers are fed in little endian
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u8(&mut self, x: u8) {
+        self.write(&[x]);
+    }
+
+    fn write_u32(&mut self, x: u32) {
+        self.write(&x.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, x: u64) {
+        self.write(&x.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Swizzle {
     X,
@@ -372,3 +945,132 @@ pub enum Swizzle {
     Z,
     W,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bool, Float, GlFloat, GlLoopVars};
+
+    #[test]
+    fn fold_constants() {
+        let graph = ShaderGraph::collect(|| {
+            let not = !Bool::from(false);
+            let and = Bool::from(true) & false;
+            Float::from(not).select(Float::from(and), Float::from(1.0).lt(2.0))
+        })
+        .unwrap();
+
+        let ops = graph.iter().map(|(_, op, _)| op).collect::<Vec<_>>();
+        assert!(ops.contains(&Op::LitBool(false)));
+        assert!(!ops
+            .iter()
+            .any(|op| matches!(op, Op::Not(_) | Op::And(_, _) | Op::Lt(_, _))));
+
+        // the select picks the first branch and is bypassed
+        let (result, _) = graph.get(graph.result());
+        assert!(matches!(result, Op::CastFloat(_)));
+    }
+
+    #[test]
+    fn fold_nested_select() {
+        let graph = ShaderGraph::collect(|| {
+            let inner = Float::from(1.0).select(2.0, !Bool::from(true));
+            inner.select(3.0, Bool::from(true) | false)
+        })
+        .unwrap();
+
+        assert_eq!(graph.get(graph.result()).0, &Op::LitFloat(2.0));
+        assert!(!graph
+            .usages()
+            .keys()
+            .any(|addr| matches!(graph.get(*addr).0, Op::Select(_, _, _))));
+    }
+
+    #[test]
+    fn fold_keeps_slot_selects() {
+        let graph = ShaderGraph::collect(|| {
+            let (x,) =
+                (Float::from(0.0),).run_loop(|(x,)| x.lt(Float::input_raw(0)), |(x,)| (x + 1.0,));
+            x.select(1.0, true)
+        })
+        .unwrap();
+
+        assert!(matches!(graph.get(graph.result()).0, Op::Select(_, _, _)));
+    }
+
+    #[test]
+    fn type_check_error() {
+        let result = ShaderGraph::collect(|| {
+            let int = push_op(Op::LitInt(1), ValueType::Int1);
+            let float = push_op(Op::LitFloat(1.0), ValueType::Float1);
+            Float::wrap(push_op(Op::Add(int, float), ValueType::Float1))
+        });
+
+        match result {
+            Err(GraphError::TypeCheck { index, op, .. }) => {
+                assert_eq!((index, op.as_str()), (2, "Add($0, $1)"))
+            }
+            _ => panic!("expected a type check error"),
+        }
+    }
+
+    #[test]
+    fn forward_reference_error() {
+        let result = ShaderGraph::collect(|| {
+            let ahead = OpAddr(1, PhantomData);
+            Float::wrap(push_op(Op::Neg(ahead), ValueType::Float1))
+        });
+
+        match result {
+            Err(GraphError::ForwardReference {
+                index, dependency, ..
+            }) => assert_eq!((index, dependency), (0, 1)),
+            _ => panic!("expected a forward reference error"),
+        }
+    }
+
+    #[test]
+    fn collect_guard_restores_outer_graph() {
+        let graph = ShaderGraph::collect(|| {
+            let x = Float::from(1.0);
+            let inner = std::panic::catch_unwind(|| {
+                ShaderGraph::collect(|| -> Float { panic!("inner shader panicked") })
+            });
+            assert!(inner.is_err());
+            x + 2.0
+        })
+        .unwrap();
+
+        assert_eq!(graph.iter().len(), 3);
+        assert!(CURRENT_GRAPH.with(|graph| graph.borrow().is_none()));
+    }
+
+    #[test]
+    fn stable_hash_is_pinned() {
+        let graph = ShaderGraph::collect(|| Float::from(1.0) + Float::input_raw(0)).unwrap();
+        // changing this value invalidates every hash persisted by users, only do it on purpose
+        assert_eq!(graph.stable_hash(), 0xb7ae6d31907fab1b);
+    }
+
+    #[test]
+    fn to_dot() {
+        let graph = ShaderGraph::collect(|| -Float::from(1.0)).unwrap();
+        let dot = graph.to_dot();
+
+        assert!(dot.starts_with("digraph shader {"));
+        assert!(dot.contains("n0 [label=\"LitFloat(1.0): Float1\", fillcolor=lightblue];"));
+        assert!(dot.contains("n0 -> n1;"));
+        assert!(dot.contains("n1 [penwidth=3];"));
+    }
+
+    #[test]
+    fn structural_equality() {
+        let shader = |x: f32| move || Float::input_raw(0) * x;
+        let a = ShaderGraph::collect(shader(2.0)).unwrap();
+        let b = ShaderGraph::collect(shader(2.0)).unwrap();
+        let c = ShaderGraph::collect(shader(3.0)).unwrap();
+
+        assert!(a == b);
+        assert!(a != c);
+    }
+}
@@ -1,12 +1,19 @@
+extern crate self as picodraw;
+
+pub mod noise;
 pub mod opengl;
+pub mod widgets;
 
 mod data;
 mod graph;
+mod matrix;
 mod shader;
 mod types;
 
-pub use data::{ShaderData, ShaderDataWriter, ShaderVars};
+pub use data::{BitFlags, BitFlagsVars, ShaderData, ShaderDataWriter, ShaderVars};
+pub use graph::{GraphError, ValueType};
 pub use image;
+pub use matrix::{Float2x2, Float3x3};
 pub use picodraw_derive::ShaderData;
-pub use shader::{Bounds, Shader, ShaderContext};
+pub use shader::{Bounds, Rounding, Shader, ShaderContext};
 pub use types::{Bool, Float, Float2, Float3, Float4, GlFloat, GlLoopVars, Int, Texture};
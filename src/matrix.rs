@@ -0,0 +1,229 @@
+use crate::{Float, Float2, Float3, GlFloat};
+use std::ops::Mul;
+
+/// A column-major 2x2 matrix, lowered to scalar vector ops.
+#[derive(Clone, Copy)]
+pub struct Float2x2 {
+    pub cols: [Float2; 2],
+}
+
+impl Float2x2 {
+    #[track_caller]
+    pub fn new(c0: impl Into<Float2>, c1: impl Into<Float2>) -> Self {
+        Self {
+            cols: [c0.into(), c1.into()],
+        }
+    }
+
+    #[track_caller]
+    pub fn identity() -> Self {
+        Self::scale(1.0, 1.0)
+    }
+
+    #[track_caller]
+    pub fn scale(x: impl Into<Float>, y: impl Into<Float>) -> Self {
+        Self::new(Float2::new(x, 0.0), Float2::new(0.0, y))
+    }
+
+    /// Counter-clockwise rotation by `angle` radians (clockwise on screen, as y points down).
+    #[track_caller]
+    pub fn rotation(angle: impl Into<Float>) -> Self {
+        let angle = angle.into();
+        let (sin, cos) = (angle.sin(), angle.cos());
+        Self::new(Float2::new(cos, sin), Float2::new(-sin, cos))
+    }
+
+    #[track_caller]
+    pub fn row(self, i: usize) -> Float2 {
+        Float2::new(lane2(self.cols[0], i), lane2(self.cols[1], i))
+    }
+
+    #[track_caller]
+    pub fn transpose(self) -> Self {
+        Self::new(self.row(0), self.row(1))
+    }
+
+    #[track_caller]
+    pub fn determinant(self) -> Float {
+        let [a, b] = self.cols;
+        a.x() * b.y() - b.x() * a.y()
+    }
+
+    /// Closed form inverse. The result is not finite if the matrix is singular.
+    #[track_caller]
+    pub fn inverse(self) -> Self {
+        let [a, b] = self.cols;
+        let inv_det = 1.0 / self.determinant();
+        Self::new(
+            Float2::new(b.y(), -a.y()) * inv_det,
+            Float2::new(-b.x(), a.x()) * inv_det,
+        )
+    }
+}
+
+impl Mul<Float2> for Float2x2 {
+    type Output = Float2;
+    #[track_caller]
+    fn mul(self, rhs: Float2) -> Self::Output {
+        self.cols[0] * rhs.x() + self.cols[1] * rhs.y()
+    }
+}
+
+impl Mul<Float2x2> for Float2x2 {
+    type Output = Float2x2;
+    #[track_caller]
+    fn mul(self, rhs: Float2x2) -> Self::Output {
+        Self::new(self * rhs.cols[0], self * rhs.cols[1])
+    }
+}
+
+impl Mul<Float> for Float2x2 {
+    type Output = Float2x2;
+    #[track_caller]
+    fn mul(self, rhs: Float) -> Self::Output {
+        Self::new(self.cols[0] * rhs, self.cols[1] * rhs)
+    }
+}
+
+/// A column-major 3x3 matrix, lowered to scalar vector ops.
+///
+/// Also usable as a 2d affine transform in homogeneous coordinates,
+/// see [`translation`](Self::translation) and [`transform_point`](Self::transform_point).
+#[derive(Clone, Copy)]
+pub struct Float3x3 {
+    pub cols: [Float3; 3],
+}
+
+impl Float3x3 {
+    #[track_caller]
+    pub fn new(c0: impl Into<Float3>, c1: impl Into<Float3>, c2: impl Into<Float3>) -> Self {
+        Self {
+            cols: [c0.into(), c1.into(), c2.into()],
+        }
+    }
+
+    #[track_caller]
+    pub fn identity() -> Self {
+        Self::scale(1.0, 1.0, 1.0)
+    }
+
+    #[track_caller]
+    pub fn scale(x: impl Into<Float>, y: impl Into<Float>, z: impl Into<Float>) -> Self {
+        Self::new(
+            Float3::new(x, 0.0, 0.0),
+            Float3::new(0.0, y, 0.0),
+            Float3::new(0.0, 0.0, z),
+        )
+    }
+
+    #[track_caller]
+    pub fn translation(x: impl Into<Float>, y: impl Into<Float>) -> Self {
+        Self::new(
+            Float3::new(1.0, 0.0, 0.0),
+            Float3::new(0.0, 1.0, 0.0),
+            Float3::new(x, y, 1.0),
+        )
+    }
+
+    /// Embeds a 2x2 linear transform into the upper left corner.
+    #[track_caller]
+    pub fn from_2x2(m: Float2x2) -> Self {
+        let [a, b] = m.cols;
+        Self::new(
+            Float3::new(a.x(), a.y(), 0.0),
+            Float3::new(b.x(), b.y(), 0.0),
+            Float3::new(0.0, 0.0, 1.0),
+        )
+    }
+
+    #[track_caller]
+    pub fn row(self, i: usize) -> Float3 {
+        Float3::new(
+            lane3(self.cols[0], i),
+            lane3(self.cols[1], i),
+            lane3(self.cols[2], i),
+        )
+    }
+
+    #[track_caller]
+    pub fn transpose(self) -> Self {
+        Self::new(self.row(0), self.row(1), self.row(2))
+    }
+
+    #[track_caller]
+    pub fn determinant(self) -> Float {
+        let [a, b, c] = self.cols;
+        a.dot(b.cross(c))
+    }
+
+    /// Closed form inverse (adjugate over determinant). The result is not finite if the matrix is singular.
+    #[track_caller]
+    pub fn inverse(self) -> Self {
+        let [a, b, c] = self.cols;
+        let r0 = b.cross(c);
+        let r1 = c.cross(a);
+        let r2 = a.cross(b);
+        let inv_det = 1.0 / a.dot(r0);
+
+        Self::new(r0 * inv_det, r1 * inv_det, r2 * inv_det).transpose()
+    }
+
+    /// Transforms a 2d point, treating it as `(x, y, 1)`.
+    #[track_caller]
+    pub fn transform_point(self, p: impl Into<Float2>) -> Float2 {
+        let p = p.into();
+        let r = self * Float3::new(p.x(), p.y(), 1.0);
+        Float2::new(r.x(), r.y()) / r.z()
+    }
+
+    /// Transforms a 2d direction, treating it as `(x, y, 0)`.
+    #[track_caller]
+    pub fn transform_vector(self, v: impl Into<Float2>) -> Float2 {
+        let v = v.into();
+        let r = self * Float3::new(v.x(), v.y(), 0.0);
+        Float2::new(r.x(), r.y())
+    }
+}
+
+impl Mul<Float3> for Float3x3 {
+    type Output = Float3;
+    #[track_caller]
+    fn mul(self, rhs: Float3) -> Self::Output {
+        self.cols[0] * rhs.x() + self.cols[1] * rhs.y() + self.cols[2] * rhs.z()
+    }
+}
+
+impl Mul<Float3x3> for Float3x3 {
+    type Output = Float3x3;
+    #[track_caller]
+    fn mul(self, rhs: Float3x3) -> Self::Output {
+        Self::new(self * rhs.cols[0], self * rhs.cols[1], self * rhs.cols[2])
+    }
+}
+
+impl Mul<Float> for Float3x3 {
+    type Output = Float3x3;
+    #[track_caller]
+    fn mul(self, rhs: Float) -> Self::Output {
+        Self::new(self.cols[0] * rhs, self.cols[1] * rhs, self.cols[2] * rhs)
+    }
+}
+
+#[track_caller]
+fn lane2(v: Float2, i: usize) -> Float {
+    match i {
+        0 => v.x(),
+        1 => v.y(),
+        _ => panic!("row index out of range: {}", i),
+    }
+}
+
+#[track_caller]
+fn lane3(v: Float3, i: usize) -> Float {
+    match i {
+        0 => v.x(),
+        1 => v.y(),
+        2 => v.z(),
+        _ => panic!("row index out of range: {}", i),
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{Float, Float2, Float3, GlFloat};
+use std::f32::consts::TAU;
+
+/// Hashes a 2d point into a pseudo-random value in `[0, 1)`.
+///
+/// This avoids the usual `fract(sin(x) * big)` trick, as `sin` precision varies between drivers
+/// and would make the output differ from GPU to GPU.
+#[track_caller]
+pub fn hash21(p: impl Into<Float2>) -> Float {
+    let p = p.into();
+    let p3 = (Float3::new(p.x(), p.y(), p.x()) * 0.1031).fract();
+    let p3 = p3 + p3.dot(Float3::new(p3.y(), p3.z(), p3.x()) + 33.33);
+    ((p3.x() + p3.y()) * p3.z()).fract()
+}
+
+/// Value noise in `[0, 1]` with one random value per integer lattice point.
+#[track_caller]
+pub fn value_noise(p: impl Into<Float2>) -> Float {
+    let p = p.into();
+    let i = p.floor();
+    let f = p.fract();
+
+    let a = hash21(i);
+    let b = hash21(i + Float2::new(1.0, 0.0));
+    let c = hash21(i + Float2::new(0.0, 1.0));
+    let d = hash21(i + Float2::new(1.0, 1.0));
+
+    let u = f * f * (3.0 - 2.0 * f);
+    u.y().lerp(u.x().lerp(a, b), u.x().lerp(c, d))
+}
+
+/// Perlin (gradient) noise, roughly in `[-0.7, 0.7]`.
+#[track_caller]
+pub fn perlin(p: impl Into<Float2>) -> Float {
+    #[track_caller]
+    fn gradient(i: Float2, f: Float2) -> Float {
+        let angle = hash21(i) * TAU;
+        Float2::new(angle.cos(), angle.sin()).dot(f)
+    }
+
+    let p = p.into();
+    let i = p.floor();
+    let f = p.fract();
+
+    let a = gradient(i, f);
+    let b = gradient(i + Float2::new(1.0, 0.0), f - Float2::new(1.0, 0.0));
+    let c = gradient(i + Float2::new(0.0, 1.0), f - Float2::new(0.0, 1.0));
+    let d = gradient(i + Float2::new(1.0, 1.0), f - Float2::new(1.0, 1.0));
+
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    u.y().lerp(u.x().lerp(a, b), u.x().lerp(c, d))
+}
+
+#[cfg(all(test, feature = "debug-graph"))]
+mod tests {
+    use super::*;
+    use crate::{graph::ShaderGraph, types::GlType};
+
+    #[test]
+    fn locations_point_at_the_caller() {
+        let line = line!() + 1;
+        let graph = ShaderGraph::collect(|| perlin(Float2::input_raw(0))).unwrap();
+
+        for (addr, op, _) in graph.iter() {
+            let location = graph.location(addr).unwrap();
+            assert_eq!(
+                (location.file(), location.line()),
+                (file!(), line),
+                "{:?}",
+                op
+            );
+        }
+    }
+}
@@ -12,6 +12,7 @@ pub type GLintptr = isize;
 pub type GLsizeiptr = isize;
 pub type GLfloat = c_float;
 pub type GLuint64 = u64;
+#[allow(clippy::upper_case_acronyms)]
 pub type GLDEBUGPROC = Option<
     extern "system" fn(
         source: GLenum,
@@ -20,7 +21,7 @@ pub type GLDEBUGPROC = Option<
         severity: GLenum,
         length: GLsizei,
         message: *const GLchar,
-        userParam: *mut c_void,
+        user_param: *mut c_void,
     ),
 >;
 
@@ -1404,6 +1405,8 @@ macro_rules! generate_bindings {
             }
 
             $(
+                // signatures mirror the raw gl functions
+                #[allow(clippy::too_many_arguments)]
                 pub unsafe fn $name(&self, $($argn: $arg,)*) $(-> $return)? {
                     (self.$name)($($argn,)*)
                 }
@@ -1415,9 +1418,16 @@ macro_rules! generate_bindings {
 generate_bindings! {
     fn get_error() -> GLenum: [glGetError];
     fn get_integer_v(name: GLenum, data: *mut GLint): [glGetIntegerv];
+    fn get_string_i(name: GLenum, index: GLuint) -> *const GLchar: [glGetStringi];
+
+    fn push_debug_group(source: GLenum, id: GLuint, length: GLsizei, message: *const GLchar): [glPushDebugGroup, glPushDebugGroupKHR];
+    fn pop_debug_group(): [glPopDebugGroup, glPopDebugGroupKHR];
 
     fn clear(mask: GLbitfield): [glClear];
+    fn clear_color(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat): [glClearColor];
     fn viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei): [glViewport];
+    fn scissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei): [glScissor];
+    fn read_pixels(x: GLint, y: GLint, width: GLsizei, height: GLsizei, format: GLenum, type_: GLenum, pixels: *mut c_void): [glReadPixels];
     fn enable(cap: GLenum): [glEnable];
     fn disable(cap: GLenum): [glDisable];
     fn blend_func_separate(srgb: GLenum, drgb: GLenum, salpha: GLenum, dalpha: GLenum): [glBlendFuncSeparate];
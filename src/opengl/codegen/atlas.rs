@@ -1,6 +1,12 @@
+use super::super::TextureSampling;
 use crunch::{Item, Rotation};
 use image::{DynamicImage, GenericImageView, RgbaImage};
-use std::{collections::HashMap, mem::swap};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    mem::swap,
+};
 
 const PADDING: u32 = 1;
 
@@ -20,23 +26,57 @@ pub struct TextureAtlas {
 pub struct ShaderTextures<'a> {
     pub index: u32,
     pub atlas: &'a TextureAtlas,
+    pub sampling: TextureSampling,
 }
 
+/// The textures of all registered shaders don't fit into a single atlas of the maximum size.
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasOverflow {
+    /// Pixels covered by the textures, including padding.
+    pub area: u64,
+    /// Largest side of a single texture, including padding.
+    pub largest: u32,
+    pub max_size: u32,
+}
+
+impl Display for AtlasOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to pack the textures ({} pixels, largest side {}) into a {}x{} atlas",
+            self.area, self.largest, self.max_size, self.max_size
+        )
+    }
+}
+
+impl Error for AtlasOverflow {}
+
 impl TextureAtlas {
-    pub fn pack<'a>(
+    pub fn pack(
         data: impl IntoIterator<Item = (u32, u32, DynamicImage)>,
         max_size: u32,
-    ) -> Self {
-        let packed = crunch::pack_into_po2(
-            max_size as usize,
-            data.into_iter().map(|item| {
+    ) -> Result<Self, AtlasOverflow> {
+        let items = data
+            .into_iter()
+            .map(|item| {
                 let (width, height) = (item.2.width() + 2 * PADDING, item.2.height() + 2 * PADDING);
                 Item::new(item, width as usize, height as usize, Rotation::Allowed)
-            }),
-        )
-        .expect("failed to pack the textures");
+            })
+            .collect::<Vec<_>>();
+
+        let overflow = AtlasOverflow {
+            area: items.iter().map(|item| (item.w * item.h) as u64).sum(),
+            largest: items
+                .iter()
+                .map(|item| item.w.max(item.h) as u32)
+                .max()
+                .unwrap_or(0),
+            max_size,
+        };
 
-        Self {
+        let packed = crunch::pack_into_po2(max_size as usize, items).map_err(|_| overflow)?;
+
+        Ok(Self {
             size: packed.w as u32,
             textures: HashMap::from_iter(packed.items.into_iter().map(|packed| {
                 (
@@ -49,17 +89,33 @@ impl TextureAtlas {
                     },
                 )
             })),
-        }
+        })
+    }
+
+    /// Pixels covered by the packed textures, including padding.
+    pub fn area_used(&self) -> u64 {
+        self.textures
+            .values()
+            .map(|tex| {
+                let w = (tex.data.width() + 2 * PADDING) as u64;
+                let h = (tex.data.height() + 2 * PADDING) as u64;
+                w * h
+            })
+            .sum()
     }
 
-    pub fn shader(&self, index: u32) -> ShaderTextures {
-        ShaderTextures { index, atlas: self }
+    pub fn shader(&self, index: u32, sampling: TextureSampling) -> ShaderTextures<'_> {
+        ShaderTextures {
+            index,
+            atlas: self,
+            sampling,
+        }
     }
 
     pub fn create_image_rgba(&self) -> RgbaImage {
         let mut image = RgbaImage::new(self.size, self.size);
 
-        for (_, tex) in &self.textures {
+        for tex in self.textures.values() {
             let x = tex.x - PADDING;
             let y = tex.y - PADDING;
             let mut w = tex.data.width() + 2 * PADDING;
@@ -96,3 +152,45 @@ impl<'a> ShaderTextures<'a> {
             .unwrap_or_else(|| panic!("unknown texture index: {}", id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn images(sizes: &[(u32, u32)]) -> Vec<(u32, u32, DynamicImage)> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(id, &(w, h))| (0, id as u32, DynamicImage::new_rgba8(w, h)))
+            .collect()
+    }
+
+    #[test]
+    fn overflow() {
+        let error = match TextureAtlas::pack(images(&[(30, 30), (30, 30), (10, 4)]), 32) {
+            Ok(_) => panic!("packed 2 30x30 textures into a 32x32 atlas"),
+            Err(error) => error,
+        };
+
+        assert_eq!(error.area, 2 * 32 * 32 + 12 * 6);
+        assert_eq!(error.largest, 32);
+        assert_eq!(error.max_size, 32);
+    }
+
+    #[test]
+    fn deterministic() {
+        let sizes = [(7, 3), (16, 16), (1, 9), (5, 5), (12, 2), (3, 3)];
+        let layout = || {
+            let atlas = TextureAtlas::pack(images(&sizes), 64).unwrap();
+            let mut textures = atlas
+                .textures
+                .iter()
+                .map(|(key, tex)| (*key, tex.x, tex.y, tex.rotated))
+                .collect::<Vec<_>>();
+            textures.sort_by_key(|(key, ..)| *key);
+            (atlas.size, textures)
+        };
+
+        assert_eq!(layout(), layout());
+    }
+}
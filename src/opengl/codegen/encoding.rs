@@ -14,7 +14,7 @@ pub struct InputStructure {
     pub size: u32,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum InputRepr {
     UInt8,
     UInt16,
@@ -42,6 +42,16 @@ impl InputStructure {
         let vars = T::shader_vars(&mut collector);
         (collector.finish(), vars)
     }
+
+    pub fn same_layout(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.inputs.len() == other.inputs.len()
+            && self
+                .inputs
+                .iter()
+                .zip(&other.inputs)
+                .all(|(a, b)| a.offset == b.offset && a.repr == b.repr)
+    }
 }
 
 impl InputCollector {
@@ -75,7 +85,7 @@ impl InputCollector {
 
         self.inputs.push(InputField {
             offset: take_offset(&mut self.bitmap, size, align),
-            repr: repr.clone(),
+            repr,
         });
 
         id
@@ -154,6 +164,28 @@ pub struct InputEncoder<'a> {
     pointer: usize,
 }
 
+impl<'a> InputEncoder<'a> {
+    // byte offset of the next `count` fields if they all match `repr` and are packed back to back,
+    // in which case a bulk write is a single copy
+    fn contiguous(
+        &self,
+        count: usize,
+        size: u32,
+        repr: impl Fn(InputRepr) -> bool,
+    ) -> Option<usize> {
+        let fields = self
+            .structure
+            .inputs
+            .get(self.pointer..self.pointer + count)?;
+        let start = fields.first()?.offset;
+        fields
+            .iter()
+            .enumerate()
+            .all(|(i, field)| repr(field.repr) && field.offset == start + i as u32 * size)
+            .then_some(start as usize)
+    }
+}
+
 impl<'a> ShaderDataWriter for InputEncoder<'a> {
     fn write_int(&mut self, x: i32) {
         let field = match self.structure.inputs.get(self.pointer) {
@@ -186,7 +218,10 @@ impl<'a> ShaderDataWriter for InputEncoder<'a> {
                 let ints = bytemuck::cast_slice_mut::<_, u32>(self.data);
                 ints[(field.offset / 4) as usize] = x as u32;
             }
-            _ => panic!("invalid shader data structure: write/read type mismatch"),
+            repr => panic!(
+                "invalid shader data structure: write/read type mismatch (field {} is read as {:?}, written as int)",
+                self.pointer, repr
+            ),
         }
 
         self.pointer += 1;
@@ -204,12 +239,50 @@ impl<'a> ShaderDataWriter for InputEncoder<'a> {
                 ints[(field.offset / 4) as usize] = f32::to_bits(x);
             }
 
-            _ => panic!("invalid shader data structure: write/read type mismatch"),
+            repr => panic!(
+                "invalid shader data structure: write/read type mismatch (field {} is read as {:?}, written as float)",
+                self.pointer, repr
+            ),
         }
 
         self.pointer += 1;
     }
 
+    fn write_floats(&mut self, xs: &[f32]) {
+        match self.contiguous(xs.len(), 4, |repr| repr == InputRepr::Float32) {
+            Some(offset) => {
+                let ints = bytemuck::cast_slice_mut::<_, u32>(self.data);
+                ints[offset / 4..offset / 4 + xs.len()].copy_from_slice(bytemuck::cast_slice(xs));
+                self.pointer += xs.len();
+            }
+            None => xs.iter().for_each(|x| self.write_float(*x)),
+        }
+    }
+
+    fn write_ints(&mut self, xs: &[i32]) {
+        let repr = |repr| matches!(repr, InputRepr::Int32 | InputRepr::UInt32);
+        match self.contiguous(xs.len(), 4, repr) {
+            Some(offset) => {
+                let ints = bytemuck::cast_slice_mut::<_, i32>(self.data);
+                ints[offset / 4..offset / 4 + xs.len()].copy_from_slice(xs);
+                self.pointer += xs.len();
+            }
+            None => xs.iter().for_each(|x| self.write_int(*x)),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let repr = |repr| matches!(repr, InputRepr::Int8 | InputRepr::UInt8);
+        match self.contiguous(bytes.len(), 1, repr) {
+            Some(offset) => {
+                let data = bytemuck::cast_slice_mut::<_, u8>(self.data);
+                data[offset..offset + bytes.len()].copy_from_slice(bytes);
+                self.pointer += bytes.len();
+            }
+            None => bytes.iter().for_each(|x| self.write_uint8(*x)),
+        }
+    }
+
     fn resolution(&self) -> (f32, f32) {
         self.resolution
     }
@@ -218,12 +291,18 @@ impl<'a> ShaderDataWriter for InputEncoder<'a> {
 pub struct QuadEncoder {
     pub quads: Vec<QuadEncoded>,
     pub data: Vec<[u32; 4]>,
+    pub culled: u32,
+    pub layered: bool,
+    pub opacity: u16,
 }
 
 pub struct QuadEncoded {
     pub bounds: [u16; 4],
     pub shader_id: u32,
     pub data_range: Range<usize>,
+    pub z: f32,
+    pub opacity: u16,
+    pub opaque: bool,
 }
 
 impl QuadEncoder {
@@ -231,12 +310,22 @@ impl QuadEncoder {
         Self {
             quads: vec![],
             data: vec![],
+            culled: 0,
+            layered: false,
+            opacity: u16::MAX,
         }
     }
 
+    pub fn reserve(&mut self, quads: usize, input: &InputStructure) {
+        self.quads.reserve(quads);
+        self.data.reserve(quads * input.size.div_ceil(16) as usize);
+    }
+
     pub fn clear(&mut self) {
         self.quads.clear();
         self.data.clear();
+        self.culled = 0;
+        self.layered = false;
     }
 
     pub fn push<T: Shader>(
@@ -244,37 +333,77 @@ impl QuadEncoder {
         draw: &T,
         shader_id: u32,
         bounds: Bounds,
+        z: f32,
         input: &InputStructure,
-        width: f32,
-        height: f32,
+        (width, height): (f32, f32),
     ) {
-        let bounds = [
-            bounds.left.min(width.ceil() as u16),
-            bounds.top.min(height.ceil() as u16),
-            bounds.right.min(width.ceil() as u16),
-            bounds.bottom.min(height.ceil() as u16),
-        ];
-
-        if bounds[0] != bounds[2] && bounds[1] != bounds[3] {
-            let data_start = self.data.len();
-            self.data
-                .resize(self.data.len() + input.size.div_ceil(16) as usize, [0; 4]);
-
-            draw.write(&mut InputEncoder {
-                data: &mut self.data[data_start..],
-                structure: input,
-                resolution: (width, height),
-                pointer: 0,
-            });
-
-            self.quads.push(QuadEncoded {
-                bounds,
-                shader_id,
-                data_range: data_start..self.data.len(),
-            });
+        let target = Bounds {
+            top: 0,
+            left: 0,
+            bottom: height.ceil() as u16,
+            right: width.ceil() as u16,
+        };
+
+        let bounds = match bounds.intersect(target) {
+            Some(bounds) => bounds,
+            None => {
+                self.culled += 1;
+                return;
+            }
+        };
+
+        let data_start = self.data.len();
+        self.data
+            .resize(self.data.len() + input.size.div_ceil(16) as usize, [0; 4]);
+
+        let mut encoder = InputEncoder {
+            data: &mut self.data[data_start..],
+            structure: input,
+            resolution: (width, height),
+            pointer: 0,
+        };
+
+        draw.write(&mut encoder);
+        debug_assert!(
+            encoder.pointer == input.inputs.len(),
+            "shader data layout mismatch: wrote {} fields, expected {}",
+            encoder.pointer,
+            input.inputs.len()
+        );
+
+        self.quads.push(QuadEncoded {
+            bounds: [bounds.left, bounds.top, bounds.right, bounds.bottom],
+            shader_id,
+            data_range: data_start..self.data.len(),
+            z,
+            opacity: self.opacity,
+            opaque: T::OPAQUE && self.opacity == u16::MAX,
+        });
+
+        if z != 0.0 {
+            self.layered = true;
         }
     }
 
+    // stable sort by z (back to front), keeping quad data contiguous in the new order
+    pub fn sort_by_z(&mut self) {
+        if !self.layered {
+            return;
+        }
+
+        self.quads.sort_by(|a, b| a.z.total_cmp(&b.z));
+
+        let mut data = Vec::with_capacity(self.data.len());
+        for quad in &mut self.quads {
+            let start = data.len();
+            data.extend_from_slice(&self.data[quad.data_range.clone()]);
+            quad.data_range = start..data.len();
+        }
+
+        self.data = data;
+        self.layered = false;
+    }
+
     pub fn size_texels(&self) -> usize {
         self.quads.len() + self.data.len()
     }
@@ -290,3 +419,51 @@ impl QuadEncoder {
             .sum()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::ShaderGraph, Float4, ShaderContext};
+
+    // reads two fields but only writes one
+    struct Short;
+
+    impl ShaderData for Short {
+        type ShaderVars = (Float, Float);
+
+        fn shader_vars(vars: &mut dyn ShaderVars) -> Self::ShaderVars {
+            (vars.read_float(), vars.read_float())
+        }
+
+        fn write(&self, writer: &mut dyn ShaderDataWriter) {
+            writer.write_float(1.0);
+        }
+    }
+
+    impl Shader for Short {
+        fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+            (shader.0 + shader.1).into()
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "shader data layout mismatch: wrote 1 fields, expected 2")]
+    fn fewer_writes_than_reads() {
+        let mut input = None;
+        ShaderGraph::collect(|| {
+            input = Some(InputStructure::of::<Short>().0);
+            Float::from(0.0)
+        })
+        .unwrap();
+
+        QuadEncoder::new().push(
+            &Short,
+            0,
+            Bounds::infinite(),
+            0.0,
+            &input.unwrap(),
+            (16.0, 16.0),
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use super::{
+    super::TextureSampling,
     atlas::{ShaderTextures, TextureAtlas},
     encoding::{
         InputField, InputRepr, InputStructure, BUILTIN_BOUNDS, BUILTIN_POSITION, BUILTIN_RESOLUTION,
@@ -23,6 +24,7 @@ uniform vec2 uResolution;
 flat out int fragType;
 flat out int fragData;
 flat out vec4 fragBounds;
+flat out float fragOpacity;
 out vec2 fragPosition;
 void main() {
     int triangleId = gl_VertexID / 3;
@@ -36,7 +38,8 @@ void main() {
     gl_Position = vec4((2.0 * pos / uResolution - 1.0) * vec2(1.0, -1.0), 0.0, 1.0);
     fragPosition = pos;
     fragBounds = vec4(topLeft, bottomRight);
-    fragType = int(packedData.z);
+    fragType = int(packedData.z & 65535u);
+    fragOpacity = float(packedData.z >> 16) / 65535.0;
     fragData = uBufferOffsetData + int(packedData.w);    
 }"#;
 
@@ -49,15 +52,23 @@ uniform vec2 uResolution;
 flat in int fragType;
 flat in int fragData;
 flat in vec4 fragBounds;
+flat in float fragOpacity;
 in vec2 fragPosition;
 out vec4 outColor;
 int uint2int(uint x,uint m){return int(x)-int((x&m)<<1);}
+int idiv(int a,int b){return b==0?0:a/b;}
+int irem(int a,int b){return b==0?0:a-b*(a/b);}
+float fpow(float x,float y){return x>=0.0?pow(x,y):y!=floor(y)?intBitsToFloat(-1):mod(y,2.0)==1.0?-pow(-x,y):pow(-x,y);}
+vec2 fpow(vec2 x,vec2 y){return vec2(fpow(x.x,y.x),fpow(x.y,y.y));}
+vec3 fpow(vec3 x,vec3 y){return vec3(fpow(x.x,y.x),fpow(x.y,y.y),fpow(x.z,y.z));}
+vec4 fpow(vec4 x,vec4 y){return vec4(fpow(x.x,y.x),fpow(x.y,y.y),fpow(x.z,y.z),fpow(x.w,y.w));}
 void main(){
 "#;
 
 pub fn generate_fragment_shader<'a>(
     graphs: impl IntoIterator<Item = (u32, &'a ShaderGraph<Float4>, &'a InputStructure)>,
     atlas: &TextureAtlas,
+    sampling: TextureSampling,
 ) -> String {
     let mut result = String::from(FRAGMENT_SHADER_HEADER);
 
@@ -71,14 +82,14 @@ pub fn generate_fragment_shader<'a>(
         let inputs = emit_decoder(
             &mut result,
             |f, offset| write!(f, "texelFetch(uBuffer,fragData+{})", offset),
-            &input,
+            input,
         )
         .unwrap();
 
         emit_graph_function(
             &mut result,
             graph,
-            atlas.shader(key),
+            atlas.shader(key, sampling),
             |f, v| match v {
                 BUILTIN_POSITION => write!(f, "fragPosition"),
                 BUILTIN_RESOLUTION => write!(f, "uResolution"),
@@ -92,7 +103,7 @@ pub fn generate_fragment_shader<'a>(
         write!(result, "}}").ok();
     }
 
-    write!(result, "}}").ok();
+    write!(result, "outColor.a*=fragOpacity;}}").ok();
 
     result
 }
@@ -190,7 +201,7 @@ fn emit_decoder_for_type(
         }
 
         InputRepr::Int32 => {
-            write!(f, "int(")?;
+            write!(f, "int {id}=int(")?;
             extract32(f, field.offset)?;
             write!(f, ");")?;
         }
@@ -230,37 +241,31 @@ fn emit_graph_function(
     mut write_input: impl FnMut(&mut dyn Write, usize) -> fmt::Result,
     mut write_output: impl FnMut(&mut dyn Write, &str) -> fmt::Result,
 ) -> fmt::Result {
-    // usage analysis
-    let usages = {
-        let mut usages = HashMap::<OpAddr, u32, _>::new();
-        *usages.entry(graph.result()).or_default() += 1;
-        for (id, op, _) in graph.iter().rev() {
-            if matches!(op, Op::SlotUpdate(_, _) | Op::LoopPush(_) | Op::LoopPop) {
-                *usages.entry(id).or_default() += 1;
-            }
+    let mut usages = graph.usages();
 
-            if usages.contains_key(&id) {
-                op.visit_dependencies(|dep| {
-                    *usages.entry(dep).or_default() += 1;
-                });
+    // expanded powers repeat their operand, so it has to be bound to a variable instead of inlined
+    for (id, op, _) in graph.iter() {
+        if let Op::Pow(a, b) = op {
+            if usages.contains_key(&id) && literal_exponent(graph, b).is_some_and(|n| n > 1) {
+                *usages.entry(a).or_default() += 1;
             }
         }
-        usages
-    };
+    }
 
     let mut atoms = HashMap::new();
     for (id, op, ty) in graph.iter() {
         let usages = usages.get(&id).copied().unwrap_or_default();
 
         match op {
-            Op::Input(ident) => {
-                if ty != ValueType::Texture {
-                    let mut string = String::new();
-                    write_input(&mut string, ident)?;
-                    atoms.insert(id, string);
-                }
+            Op::Input(ident) if ty != ValueType::Texture => {
+                let mut string = String::new();
+                write_input(&mut string, ident)?;
+                atoms.insert(id, string);
             }
 
+            // textures are only referenced by the sampling ops
+            Op::Input(_) => {}
+
             Op::SlotCreate(init) => {
                 let name = format!("_{:x}", id.id());
                 write!(
@@ -288,9 +293,12 @@ fn emit_graph_function(
             Op::LitFloat(_) | Op::LitInt(_) | Op::LitBool(_) => {
                 let mut f = String::new();
                 match op {
-                    Op::LitFloat(f32::INFINITY) => write!(f, "uintBitsToFloat(0x7F800000)")?,
-                    Op::LitFloat(f32::NEG_INFINITY) => write!(f, "uintBitsToFloat(0xFF800000)")?,
+                    // GLSL 3.30 has no implicit int to uint conversion, the literals need the `u` suffix
+                    Op::LitFloat(f32::INFINITY) => write!(f, "uintBitsToFloat(0x7F800000u)")?,
+                    Op::LitFloat(f32::NEG_INFINITY) => write!(f, "uintBitsToFloat(0xFF800000u)")?,
                     Op::LitFloat(x) if x.is_nan() => write!(f, "intBitsToFloat(-1)")?,
+                    // `Debug` prints the shortest representation that round-trips to the same f32,
+                    // and always includes a `.` or an exponent, so GLSL never parses it as an int
                     Op::LitFloat(x) if x.is_sign_positive() => write!(f, "{x:?}")?,
                     Op::LitFloat(x) => write!(f, "({x:?})")?,
                     Op::LitInt(x) if x >= 0 => write!(f, "{x}")?,
@@ -304,7 +312,7 @@ fn emit_graph_function(
 
             _ if usages == 1 => {
                 let mut string = String::new();
-                emit_graph_atom(&mut string, op, graph, atlas, |f, value| {
+                emit_graph_atom(&mut string, op, ty, graph, atlas, |f, value| {
                     write!(f, "{}", atoms.get(&value).unwrap())
                 })?;
                 atoms.insert(id, string);
@@ -314,7 +322,7 @@ fn emit_graph_function(
                 let name = format!("_{:x}", id.id());
 
                 write!(f, "{} {}=", type_name(ty), name)?;
-                emit_graph_atom(f, op, graph, atlas, |f, value| {
+                emit_graph_atom(f, op, ty, graph, atlas, |f, value| {
                     write!(f, "{}", atoms.get(&value).unwrap())
                 })?;
                 write!(f, ";")?;
@@ -331,9 +339,10 @@ fn emit_graph_function(
     Ok(())
 }
 
-fn emit_graph_atom<'a>(
+fn emit_graph_atom(
     f: &mut dyn Write,
     op: Op,
+    ty: ValueType,
     graph: &ShaderGraph<Float4>,
     atlas: ShaderTextures,
     mut dep: impl FnMut(&mut dyn Write, OpAddr) -> fmt::Result,
@@ -360,6 +369,13 @@ fn emit_graph_atom<'a>(
             dep(f, b)?;
             write!(f, ")")?
         }
+        Op::Div(a, b) if ty.is_int() => {
+            write!(f, "idiv(")?;
+            dep(f, a)?;
+            write!(f, ",")?;
+            dep(f, b)?;
+            write!(f, ")")?
+        }
         Op::Div(a, b) => {
             write!(f, "(")?;
             dep(f, a)?;
@@ -367,6 +383,13 @@ fn emit_graph_atom<'a>(
             dep(f, b)?;
             write!(f, ")")?
         }
+        Op::Rem(a, b) if ty.is_int() => {
+            write!(f, "irem(")?;
+            dep(f, a)?;
+            write!(f, ",")?;
+            dep(f, b)?;
+            write!(f, ")")?
+        }
         Op::Rem(a, b) => {
             write!(f, "mod(")?;
             dep(f, a)?;
@@ -435,8 +458,21 @@ fn emit_graph_atom<'a>(
             dep(f, a)?;
             write!(f, ")")?;
         }
+        Op::Pow(a, b) if literal_exponent(graph, b).is_some() => {
+            // small integer powers are expanded into multiplications, which are faster than fpow
+            let n = literal_exponent(graph, b).unwrap();
+            write!(f, "(")?;
+            for i in 0..n {
+                if i > 0 {
+                    write!(f, "*")?;
+                }
+                dep(f, a)?;
+            }
+            write!(f, ")")?;
+        }
         Op::Pow(a, b) => {
-            write!(f, "pow(")?;
+            // glsl pow is undefined for negative bases, fpow matches f32::powf instead
+            write!(f, "fpow(")?;
             dep(f, a)?;
             write!(f, ",")?;
             dep(f, b)?;
@@ -466,6 +502,16 @@ fn emit_graph_atom<'a>(
             dep(f, b)?;
             write!(f, ")")?;
         }
+        Op::Clamp(x, min, max) if !ordered_literals(graph, min, max) => {
+            // glsl clamp is undefined for min > max, this always returns min in that case
+            write!(f, "max(")?;
+            dep(f, min)?;
+            write!(f, ",min(")?;
+            dep(f, x)?;
+            write!(f, ",")?;
+            dep(f, max)?;
+            write!(f, "))")?;
+        }
         Op::Clamp(x, min, max) => {
             write!(f, "clamp(")?;
             dep(f, x)?;
@@ -711,10 +757,15 @@ fn emit_graph_atom<'a>(
                 ("", texture.data.width(), texture.data.height())
             };
 
+            let offset = match atlas.sampling {
+                TextureSampling::PixelCenter => "",
+                TextureSampling::PixelCorner => "0.5+",
+            };
+
             write!(
                 f,
-                "texture(uAtlas,(vec2({}.0,{}.0)+clamp(0.5+",
-                texture.x, texture.y
+                "texture(uAtlas,(vec2({}.0,{}.0)+clamp({}",
+                texture.x, texture.y, offset
             )?;
             dep(f, b)?;
             write!(
@@ -736,10 +787,15 @@ fn emit_graph_atom<'a>(
                 ("", texture.data.width(), texture.data.height())
             };
 
+            let offset = match atlas.sampling {
+                TextureSampling::PixelCenter => "",
+                TextureSampling::PixelCorner => "0.5+",
+            };
+
             write!(
                 f,
-                "texelFetch(uAtlas,ivec2({},{})+clamp(ivec2(",
-                texture.x, texture.y
+                "texelFetch(uAtlas,ivec2({},{})+clamp(ivec2({}",
+                texture.x, texture.y, offset
             )?;
             dep(f, b)?;
             write!(f, "){},ivec2(0),ivec2({},{})),0)", sample, w, h)?;
@@ -765,6 +821,30 @@ fn emit_graph_atom<'a>(
     Ok(())
 }
 
+fn ordered_literals(graph: &ShaderGraph<Float4>, min: OpAddr, max: OpAddr) -> bool {
+    fn literal(graph: &ShaderGraph<Float4>, value: OpAddr) -> Option<f64> {
+        match graph.get(value) {
+            (Op::LitFloat(x), _) => Some(*x as f64),
+            (Op::LitInt(x), _) => Some(*x as f64),
+            (Op::SplatVec2(x) | Op::SplatVec3(x) | Op::SplatVec4(x), _) => literal(graph, *x),
+            _ => None,
+        }
+    }
+
+    match (literal(graph, min), literal(graph, max)) {
+        (Some(min), Some(max)) => min <= max,
+        _ => false,
+    }
+}
+
+fn literal_exponent(graph: &ShaderGraph<Float4>, value: OpAddr) -> Option<u32> {
+    match graph.get(value) {
+        (Op::LitFloat(x), _) if [1.0, 2.0, 3.0, 4.0].contains(x) => Some(*x as u32),
+        (Op::SplatVec2(x) | Op::SplatVec3(x) | Op::SplatVec4(x), _) => literal_exponent(graph, *x),
+        _ => None,
+    }
+}
+
 fn type_name(ty: ValueType) -> &'static str {
     match ty {
         ValueType::Int1 => "int",
@@ -773,7 +853,111 @@ fn type_name(ty: ValueType) -> &'static str {
         ValueType::Float2 => "vec2",
         ValueType::Float3 => "vec3",
         ValueType::Float4 => "vec4",
+        ValueType::Int2 => "ivec2",
+        ValueType::Int3 => "ivec3",
+        ValueType::Int4 => "ivec4",
+        ValueType::Bool2 => "bvec2",
+        ValueType::Bool3 => "bvec3",
+        ValueType::Bool4 => "bvec4",
+
+        ValueType::Texture => unreachable!("textures are never stored in variables"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{types::GlType, Float, Float2, GlFloat, Int, Texture};
+    use image::DynamicImage;
+
+    // emits a graph with inputs named `_i{id}` and a single 2x2 texture as input 0
+    fn emit(sampling: TextureSampling, c: impl FnOnce() -> Float4) -> String {
+        let graph = ShaderGraph::collect(c).unwrap();
+        let atlas = TextureAtlas::pack([(0, 0, DynamicImage::new_rgba8(2, 2))], 16).unwrap();
+
+        let mut result = String::new();
+        emit_graph_function(
+            &mut result,
+            &graph,
+            atlas.shader(0, sampling),
+            |f, id| write!(f, "_i{:x}", id),
+            |f, expr| write!(f, "outColor={};", expr),
+        )
+        .unwrap();
+        result
+    }
+
+    #[test]
+    fn texture_sampling() {
+        let linear = || Texture::input_raw(0).linear(Float2::input_raw(1));
+        let nearest = || Texture::input_raw(0).nearest(Float2::input_raw(1));
+
+        assert!(emit(TextureSampling::PixelCorner, linear).contains("+clamp(0.5+_i1,"));
+        assert!(emit(TextureSampling::PixelCenter, linear).contains("+clamp(_i1,"));
+        assert!(emit(TextureSampling::PixelCorner, nearest).contains("+clamp(ivec2(0.5+_i1)"));
+        assert!(emit(TextureSampling::PixelCenter, nearest).contains("+clamp(ivec2(_i1)"));
+    }
+
+    #[test]
+    fn pow() {
+        let sampling = TextureSampling::default();
+        let base = || Float::input_raw(1);
+
+        let cube = emit(sampling, || base().pow(3.0).into());
+        assert!(cube.contains("(_i1*_i1*_i1)"), "{}", cube);
+
+        let fraction = emit(sampling, || base().pow(2.5).into());
+        assert!(fraction.contains("fpow(_i1,2.5)"), "{}", fraction);
+
+        let runtime = emit(sampling, || base().pow(Float::input_raw(2)).into());
+        assert!(runtime.contains("fpow(_i1,_i2)"), "{}", runtime);
+    }
+
+    #[test]
+    fn integer_division() {
+        let sampling = TextureSampling::default();
+        let div = emit(sampling, || Float::from(Int::input_raw(1) / -3).into());
+        assert!(div.contains("idiv(_i1,(-3))"), "{}", div);
+
+        let rem = emit(sampling, || {
+            Float::from(Int::from(-7) % Int::input_raw(2)).into()
+        });
+        assert!(rem.contains("irem((-7),_i2)"), "{}", rem);
+
+        assert!(FRAGMENT_SHADER_HEADER.contains("int idiv(int a,int b){return b==0?0:a/b;}"));
+        assert!(FRAGMENT_SHADER_HEADER.contains("int irem(int a,int b){return b==0?0:a-b*(a/b);}"));
+    }
+
+    #[test]
+    fn float_literals() {
+        let sampling = TextureSampling::default();
+        let literals = emit(sampling, || {
+            Float4::new(f32::INFINITY, f32::NEG_INFINITY, 0.1, -2.5)
+        });
+        assert!(
+            literals.contains(
+                "vec4(uintBitsToFloat(0x7F800000u),uintBitsToFloat(0xFF800000u),0.1,(-2.5))"
+            ),
+            "{}",
+            literals
+        );
+
+        let nan = emit(sampling, || Float::from(f32::NAN).into());
+        assert!(nan.contains("intBitsToFloat(-1)"), "{}", nan);
+    }
+
+    #[test]
+    fn clamp() {
+        let sampling = TextureSampling::default();
+        let x = || Float::input_raw(1);
+
+        let ordered = emit(sampling, || x().clamp(0.0, 1.0).into());
+        assert!(ordered.contains("clamp(_i1,0.0,1.0)"), "{}", ordered);
+
+        let reversed = emit(sampling, || x().clamp(1.0, 0.0).into());
+        assert!(reversed.contains("max(1.0,min(_i1,0.0))"), "{}", reversed);
 
-        _ => todo!(),
+        let runtime = emit(sampling, || x().clamp(0.0, Float::input_raw(2)).into());
+        assert!(runtime.contains("max(0.0,min(_i1,_i2))"), "{}", runtime);
     }
 }
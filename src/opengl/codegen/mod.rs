@@ -1,4 +1,9 @@
-use crate::{graph::ShaderGraph, types::GlType, Bounds, Float2, Float4, Shader, ShaderContext};
+use super::{ShaderSizeWarning, TextureSampling};
+use crate::{
+    graph::{GraphError, Op, ShaderGraph, ValueType},
+    types::GlType,
+    Bounds, Float2, Float4, Shader, ShaderContext,
+};
 use encoding::{InputStructure, BUILTIN_BOUNDS, BUILTIN_POSITION, BUILTIN_RESOLUTION};
 use rustc_hash::FxHashMap;
 use std::any::{type_name, TypeId};
@@ -7,33 +12,45 @@ mod atlas;
 mod encoding;
 mod glsl;
 
-pub use atlas::TextureAtlas;
+pub use atlas::{AtlasOverflow, TextureAtlas};
 pub use encoding::QuadEncoder;
 pub use glsl::VERTEX_SHADER;
 
 struct ShaderData {
     id: u32,
+    hash: u64,
     graph: ShaderGraph<Float4>,
     input: InputStructure,
 }
 
 pub struct ShaderMap {
-    shaders: FxHashMap<TypeId, ShaderData>,
+    // indexed by shader id
+    shaders: Vec<ShaderData>,
+    types: FxHashMap<TypeId, u32>,
     dirty: bool,
+
+    max_ops: usize,
+    max_texture_samples: usize,
+    warnings: Vec<ShaderSizeWarning>,
 }
 
 impl ShaderMap {
-    pub fn new() -> Self {
+    pub fn new(max_ops: usize, max_texture_samples: usize) -> Self {
         Self {
-            shaders: FxHashMap::default(),
+            shaders: vec![],
+            types: FxHashMap::default(),
             dirty: false,
+
+            max_ops,
+            max_texture_samples,
+            warnings: vec![],
         }
     }
 
-    pub fn register<T: Shader>(&mut self) {
-        let id = T::id();
-        if self.shaders.contains_key(&id) {
-            return;
+    pub fn register<T: Shader>(&mut self) -> Result<(), GraphError> {
+        let type_id = T::id();
+        if self.types.contains_key(&type_id) {
+            return Ok(());
         }
 
         let mut input = None;
@@ -46,28 +63,77 @@ impl ShaderMap {
                 resolution: Float2::input_raw(BUILTIN_RESOLUTION),
                 bounds: Float4::input_raw(BUILTIN_BOUNDS),
             })
+        })?;
+
+        let input = input.unwrap();
+        let hash = graph.stable_hash();
+
+        let ops = graph.iter().len();
+        let texture_samples = graph
+            .iter()
+            .filter(|(_, op, _)| {
+                matches!(
+                    op,
+                    Op::TextureSampleLinear(_, _) | Op::TextureSampleNearest(_, _)
+                )
+            })
+            .count();
+
+        if ops > self.max_ops || texture_samples > self.max_texture_samples {
+            self.warnings.push(ShaderSizeWarning {
+                shader: type_name::<T>(),
+                ops,
+                texture_samples,
+            });
+        }
+
+        // structurally identical shaders share a single id, so registering them doesn't trigger a recompile.
+        // textures can't be compared, so shaders that use them are never shared
+        let existing = self.shaders.iter().find(|data| {
+            data.hash == hash
+                && input.textures.is_empty()
+                && data.input.textures.is_empty()
+                && data.input.same_layout(&input)
+                && data.graph == graph
         });
 
+        if let Some(data) = existing {
+            self.types.insert(type_id, data.id);
+            return Ok(());
+        }
+
+        // the upper 16 bits of the quad type are used for the opacity
+        assert!(self.shaders.len() < 65536, "too many shaders registered");
+
+        let id = self.shaders.len() as u32;
         self.dirty = true;
-        self.shaders.insert(
+        self.types.insert(type_id, id);
+        self.shaders.push(ShaderData {
             id,
-            ShaderData {
-                id: self.shaders.len() as u32,
-                graph,
-                input: input.unwrap(),
-            },
-        );
+            hash,
+            graph,
+            input,
+        });
+
+        Ok(())
+    }
+
+    pub fn warnings(&self) -> &[ShaderSizeWarning] {
+        &self.warnings
     }
 
     pub fn is_dirty(&self) -> bool {
         self.dirty
     }
 
-    pub fn recompile(&mut self, max_texture_size: u32) -> (String, TextureAtlas) {
-        self.dirty = false;
-
+    pub fn recompile(
+        &mut self,
+        max_texture_size: u32,
+        sampling: TextureSampling,
+    ) -> Result<(String, TextureAtlas), AtlasOverflow> {
+        // shaders are stored in id order, so the atlas layout and the generated source are reproducible
         let atlas = TextureAtlas::pack(
-            self.shaders.values().flat_map(|data| {
+            self.shaders.iter().flat_map(|data| {
                 data.input
                     .textures
                     .iter()
@@ -75,27 +141,67 @@ impl ShaderMap {
                     .map(move |(id, generator)| (data.id, id as u32, generator()))
             }),
             max_texture_size,
-        );
+        )?;
 
         let fragment_src = glsl::generate_fragment_shader(
             self.shaders
-                .values()
+                .iter()
                 .map(|data| (data.id, &data.graph, &data.input)),
             &atlas,
+            sampling,
         );
 
-        (fragment_src, atlas)
+        self.dirty = false;
+        Ok((fragment_src, atlas))
+    }
+
+    pub fn to_dot<T: Shader>(&self) -> Option<String> {
+        let id = *self.types.get(&T::id())?;
+        Some(self.shaders[id as usize].graph.to_dot())
+    }
+
+    /// Indices (in read order) of the data fields that the shader never uses.
+    pub fn unused_inputs<T: Shader>(&self) -> Option<Vec<usize>> {
+        let id = *self.types.get(&T::id())?;
+        let data = &self.shaders[id as usize];
+
+        let usages = data.graph.usages();
+        let mut used = vec![false; data.input.inputs.len()];
+        for (addr, op, ty) in data.graph.iter() {
+            if let Op::Input(index) = op {
+                if ty != ValueType::Texture && index < used.len() && usages.contains_key(&addr) {
+                    used[index] = true;
+                }
+            }
+        }
+
+        Some((0..used.len()).filter(|i| !used[*i]).collect())
+    }
+
+    /// Number of textures used by each shader, indexed by shader id.
+    pub fn texture_counts(&self) -> Vec<u32> {
+        self.shaders
+            .iter()
+            .map(|data| data.input.textures.len() as u32)
+            .collect()
+    }
+
+    pub fn reserve<T: Shader>(&self, encoder: &mut QuadEncoder, quads: usize) {
+        if let Some(&id) = self.types.get(&T::id()) {
+            encoder.reserve(quads, &self.shaders[id as usize].input);
+        }
     }
 
     pub fn write<T: Shader>(
         &mut self,
         encoder: &mut QuadEncoder,
         bounds: Bounds,
+        z: f32,
         value: &T,
         width: u32,
         height: u32,
     ) {
-        let data = self.shaders.get(&T::id()).unwrap_or_else(|| {
+        let id = *self.types.get(&T::id()).unwrap_or_else(|| {
             if cfg!(debug_assertions) {
                 panic!("register the drawable first ({})", type_name::<T>())
             } else {
@@ -103,13 +209,142 @@ impl ShaderMap {
             }
         });
 
+        let data = &self.shaders[id as usize];
+
         encoder.push(
             value,
             data.id,
             bounds,
+            z,
             &data.input,
-            width as f32,
-            height as f32,
+            (width as f32, height as f32),
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ShaderData, ShaderDataWriter, ShaderVars, Texture};
+    use image::DynamicImage;
+    use std::sync::Arc;
+
+    #[derive(ShaderData)]
+    struct First {
+        value: f32,
+    }
+
+    impl Shader for First {
+        fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+            Float4::from(shader.value)
+        }
+    }
+
+    #[derive(ShaderData)]
+    struct Second {
+        value: f32,
+    }
+
+    impl Shader for Second {
+        fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+            Float4::from(shader.value)
+        }
+    }
+
+    #[derive(ShaderData)]
+    struct Third {
+        value: f32,
+    }
+
+    impl Shader for Third {
+        fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+            Float4::from(shader.value * 2.0)
+        }
+    }
+
+    #[test]
+    fn identical_shaders_share_an_id() {
+        let mut shaders = ShaderMap::new(usize::MAX, usize::MAX);
+
+        shaders.register::<First>().unwrap();
+        assert!(shaders.is_dirty());
+        shaders.recompile(64, TextureSampling::default()).unwrap();
+
+        shaders.register::<Second>().unwrap();
+        assert!(!shaders.is_dirty());
+        assert_eq!(shaders.types[&First::id()], shaders.types[&Second::id()]);
+        assert_eq!(shaders.shaders.len(), 1);
+
+        shaders.register::<Third>().unwrap();
+        assert!(shaders.is_dirty());
+        assert_ne!(shaders.types[&First::id()], shaders.types[&Third::id()]);
+    }
+
+    #[derive(ShaderData)]
+    struct Partial {
+        used: f32,
+        unused: f32,
+        also_used: f32,
+    }
+
+    impl Shader for Partial {
+        fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+            let _ = shader.unused + 1.0;
+            Float4::from(shader.used + shader.also_used)
+        }
+    }
+
+    #[test]
+    fn unused_inputs() {
+        let mut shaders = ShaderMap::new(usize::MAX, usize::MAX);
+        assert_eq!(shaders.unused_inputs::<Partial>(), None);
+
+        shaders.register::<Partial>().unwrap();
+        assert_eq!(shaders.unused_inputs::<Partial>(), Some(vec![1]));
+    }
+
+    struct Blur;
+
+    impl ShaderData for Blur {
+        type ShaderVars = Texture;
+
+        fn shader_vars(vars: &mut dyn ShaderVars) -> Self::ShaderVars {
+            vars.texture(Arc::new(|| DynamicImage::new_rgba8(4, 4)))
+        }
+
+        fn write(&self, _writer: &mut dyn ShaderDataWriter) {}
+    }
+
+    impl Shader for Blur {
+        fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+            (0..3)
+                .map(|i| shader.linear(shader.position + i as f32))
+                .reduce(|a, b| a + b)
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn size_warnings() {
+        let mut shaders = ShaderMap::new(8, 2);
+
+        shaders.register::<First>().unwrap();
+        assert!(shaders.warnings().is_empty());
+
+        shaders.register::<Blur>().unwrap();
+        let ops = shaders.shaders[shaders.types[&Blur::id()] as usize]
+            .graph
+            .iter()
+            .len();
+        assert!(ops > 8);
+
+        match shaders.warnings() {
+            [warning] => {
+                assert_eq!(warning.shader, type_name::<Blur>());
+                assert_eq!(warning.ops, ops);
+                assert_eq!(warning.texture_samples, 3);
+            }
+            warnings => panic!("expected a single warning, got {:?}", warnings),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use super::bindings::*;
 use std::{
     cell::Cell,
-    ffi::CString,
+    ffi::{c_void, CStr, CString},
     marker::PhantomData,
     mem::{forget, size_of},
     ops::Deref,
@@ -23,7 +23,7 @@ impl<'a> GlContext<'a> {
 impl<'a> Deref for GlContext<'a> {
     type Target = GlBindings;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.0
     }
 }
 
@@ -420,22 +420,26 @@ impl GlQuery {
         }
     }
 
-    pub fn time_elapsed(&self, gl: GlContext, c: impl FnOnce()) -> Option<u64> {
+    pub fn begin(&self, gl: GlContext) {
         unsafe {
             if self.waiting.get() == 255 {
                 gl.begin_query(TIME_ELAPSED, self.query);
                 check_error(gl);
 
-                c();
+                self.waiting.set(254);
+            }
+        }
+    }
 
+    pub fn end(&self, gl: GlContext) -> Option<u64> {
+        unsafe {
+            if self.waiting.get() == 254 {
                 gl.end_query(TIME_ELAPSED);
                 check_error(gl);
 
                 self.waiting.set(3);
                 None
             } else if self.waiting.get() == 0 {
-                c();
-
                 let mut available = 0;
 
                 gl.get_query_object_iv(self.query, QUERY_RESULT_AVAILABLE, &mut available);
@@ -452,10 +456,11 @@ impl GlQuery {
                 } else {
                     None
                 }
-            } else {
-                c();
+            } else if self.waiting.get() != 255 {
                 self.waiting.set(self.waiting.get() - 1);
                 None
+            } else {
+                None
             }
         }
     }
@@ -468,10 +473,15 @@ impl GlQuery {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct GlInfo {
     pub version: (i32, i32),
     pub max_texture_size: usize,
     pub max_texture_buffer_size: usize,
+    pub max_texture_image_units: usize,
+    pub max_texture_image_units_combined: usize,
+    /// `KHR_debug` debug groups are available (core since 4.3).
+    pub debug_groups: bool,
 }
 
 impl GlInfo {
@@ -502,6 +512,20 @@ impl GlInfo {
             );
             check_error(gl);
 
+            let mut debug_groups = version >= (4, 3);
+            if !debug_groups {
+                let mut num_extensions = 0;
+                gl.get_integer_v(NUM_EXTENSIONS, &mut num_extensions);
+                for i in 0..num_extensions.max(0) as GLuint {
+                    let name = gl.get_string_i(EXTENSIONS, i);
+                    if !name.is_null() && CStr::from_ptr(name).to_bytes() == b"GL_KHR_debug" {
+                        debug_groups = true;
+                        break;
+                    }
+                }
+            }
+            check_error(gl);
+
             // sanity checks
             if max_texture_image_units <= 0
                 || max_texture_image_units_combined <= 0
@@ -515,11 +539,33 @@ impl GlInfo {
                 version,
                 max_texture_buffer_size: max_texture_buffer_size as usize,
                 max_texture_size: max_texture_size as usize,
+                max_texture_image_units: max_texture_image_units as usize,
+                max_texture_image_units_combined: max_texture_image_units_combined as usize,
+                debug_groups,
             })
         }
     }
 }
 
+pub fn push_debug_group(gl: GlContext, message: &str) {
+    unsafe {
+        gl.push_debug_group(
+            DEBUG_SOURCE_APPLICATION,
+            0,
+            message.len() as _,
+            message.as_ptr() as *const GLchar,
+        );
+    }
+    check_error(gl);
+}
+
+pub fn pop_debug_group(gl: GlContext) {
+    unsafe {
+        gl.pop_debug_group();
+    }
+    check_error(gl);
+}
+
 pub fn draw_arrays_triangles(gl: GlContext, count: usize) {
     unsafe {
         gl.draw_arrays(TRIANGLES, 0, count as _);
@@ -556,6 +602,13 @@ pub fn enable_blend_normal(gl: GlContext) {
     check_error(gl);
 }
 
+pub fn disable_blend(gl: GlContext) {
+    unsafe {
+        gl.disable(BLEND);
+    }
+    check_error(gl);
+}
+
 pub fn enable_framebuffer_srgb(gl: GlContext) {
     unsafe {
         gl.enable(FRAMEBUFFER_SRGB);
@@ -570,11 +623,34 @@ pub fn disable_framebuffer_srgb(gl: GlContext) {
     check_error(gl);
 }
 
-pub fn clear_color(gl: GlContext) {
+// x and y are in gl window coordinates (bottom left origin)
+pub fn clear_color_rect(gl: GlContext, x: i32, y: i32, w: u32, h: u32, [r, g, b, a]: [f32; 4]) {
     unsafe {
+        gl.enable(SCISSOR_TEST);
+        gl.scissor(x as _, y as _, w as _, h as _);
+        gl.clear_color(r, g, b, a);
         gl.clear(COLOR_BUFFER_BIT);
+        gl.disable(SCISSOR_TEST);
+    }
+    check_error(gl);
+}
+
+/// Reads rgba8 pixels from the bound framebuffer, rows are bottom to top.
+pub fn read_pixels_rgba(gl: GlContext, x: i32, y: i32, w: u32, h: u32) -> Vec<u8> {
+    let mut pixels = vec![0u8; w as usize * h as usize * 4];
+    unsafe {
+        gl.read_pixels(
+            x as _,
+            y as _,
+            w as _,
+            h as _,
+            RGBA,
+            UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
     }
     check_error(gl);
+    pixels
 }
 
 pub fn bind_default_framebuffer(gl: GlContext) {
@@ -612,8 +688,8 @@ pub fn check_error(gl: GlContext) {
     }
 }
 
-struct Defer<F: FnMut() -> ()>(F);
-impl<F: FnMut() -> ()> Drop for Defer<F> {
+struct Defer<F: FnMut()>(F);
+impl<F: FnMut()> Drop for Defer<F> {
     fn drop(&mut self) {
         (self.0)()
     }
@@ -2,22 +2,49 @@ mod bindings;
 mod codegen;
 mod gllayer;
 
-use crate::{Bounds, Shader};
+use crate::{Bounds, GraphError, Shader};
 use bindings::GlBindings;
 use codegen::{QuadEncoder, ShaderMap};
 use gllayer::*;
+use image::RgbaImage;
 use std::{
     ffi::{c_void, CStr},
     mem::size_of,
 };
 
+pub use codegen::AtlasOverflow;
+pub use gllayer::GlInfo;
+
 #[derive(Debug, Clone)]
 pub struct GlStatistics {
     pub gpu_time_msec: f32,
     pub size_bytes: u64,
     pub area_pixels: u64,
     pub quads: u32,
+    pub quads_culled: u32,
     pub drawcalls: u32,
+    /// Set when the shaders were compiled during the pass and their textures didn't fit into the
+    /// atlas. The quads pending at that point are dropped, see [`OpenGl::compile`].
+    pub atlas_overflow: Option<AtlasOverflow>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GlAtlasStatistics {
+    /// Side length of the square atlas texture in pixels.
+    pub size: u32,
+    /// Pixels covered by textures, including padding.
+    pub area_used: u64,
+    /// Number of textures used by each shader, indexed by shader id.
+    pub shader_textures: Vec<u32>,
+}
+
+/// A registered shader whose graph exceeded [`OpenGlConfig::max_shader_ops`] or
+/// [`OpenGlConfig::max_shader_texture_samples`], usually because of a loop unrolled in Rust.
+#[derive(Debug, Clone)]
+pub struct ShaderSizeWarning {
+    pub shader: &'static str,
+    pub ops: usize,
+    pub texture_samples: usize,
 }
 
 pub struct OpenGl {
@@ -31,10 +58,11 @@ struct GlData {
     program: Option<GlProgramData>,
     buffer: GlTextureBuffer,
     vao: GlVertexArrayObject,
-    query: GlQuery,
+    query: Option<GlQuery>,
     info: GlInfo,
 
     shaders: ShaderMap,
+    atlas_stats: Option<GlAtlasStatistics>,
     pass_encoding: QuadEncoder,
     pass_viewport: Option<CurrentPass>,
 
@@ -53,19 +81,67 @@ struct GlProgramData {
 #[derive(Debug, Clone, Copy)]
 pub struct OpenGlConfig {
     pub srgb: bool,
+    /// Measure gpu time of every pass with a timer query. When disabled,
+    /// [`GlStatistics::gpu_time_msec`] is always 0.
+    pub profiling: bool,
+    /// Wrap each batch of draws in a `KHR_debug` group, so they are labeled in tools like RenderDoc.
+    /// Ignored when the extension is not available.
+    pub debug_groups: bool,
+    /// Which texture coordinates [`Texture::linear`](crate::Texture::linear) and
+    /// [`Texture::nearest`](crate::Texture::nearest) map to texel centers.
+    pub texture_sampling: TextureSampling,
+    /// Shaders with more graph ops than this are reported by [`OpenGl::shader_warnings`].
+    pub max_shader_ops: usize,
+    /// Shaders with more texture samples than this are reported by [`OpenGl::shader_warnings`].
+    pub max_shader_texture_samples: usize,
 }
 
 impl Default for OpenGlConfig {
     fn default() -> Self {
-        Self { srgb: false }
+        Self {
+            srgb: false,
+            profiling: true,
+            debug_groups: false,
+            texture_sampling: TextureSampling::PixelCorner,
+            max_shader_ops: 4096,
+            max_shader_texture_samples: 64,
+        }
     }
 }
 
+/// Texture coordinate convention used when sampling textures.
+///
+/// The default keeps the original convention, so existing shaders sample the same texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureSampling {
+    /// Texel `i` covers `[i, i + 1)` and its center is at `i + 0.5`, same as
+    /// [`ShaderContext::position`](crate::ShaderContext::position) and OpenGL itself.
+    /// Sampling at `position - bounds.xy` draws the texture 1:1.
+    PixelCenter,
+    /// The center of texel `i` is at `i`, so sampling at `position - bounds.xy` is off by half a texel.
+    #[default]
+    PixelCorner,
+}
+
+/// What happens to the existing framebuffer contents at the start of a pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadOp {
+    /// Keep the contents, drawing on top of them.
+    Load,
+    /// Clear to a color (straight rgba).
+    Clear([f32; 4]),
+}
+
 pub struct OpenGlRenderer<'a> {
     data: &'a mut GlData,
+    gl: GlContext<'a>,
 }
 
 impl OpenGl {
+    /// Loads the gl functions with `f` and creates the buffers used for drawing.
+    ///
+    /// # Safety
+    /// The OpenGL context `f` loads functions from must be current on the calling thread.
     pub unsafe fn new(f: &dyn Fn(&CStr) -> *const c_void, config: OpenGlConfig) -> Self {
         let bindings = GlBindings::load_from(f);
         let data = GlContext::within(&bindings, |gl| GlData::new(gl, config));
@@ -73,21 +149,142 @@ impl OpenGl {
         Self { bindings, data }
     }
 
+    /// Renders a pass, clearing the framebuffer to transparent black first.
+    ///
+    /// # Safety
+    /// The OpenGL context this was created with must be current on the calling thread.
     pub unsafe fn render(
         &mut self,
         width: u32,
         height: u32,
         c: impl for<'a> FnOnce(OpenGlRenderer<'a>),
     ) -> GlStatistics {
+        self.render_with(width, height, LoadOp::Clear([0.0; 4]), c)
+    }
+
+    /// Renders a pass over the whole framebuffer, starting with the given load operation.
+    ///
+    /// # Safety
+    /// The OpenGL context this was created with must be current on the calling thread.
+    pub unsafe fn render_with(
+        &mut self,
+        width: u32,
+        height: u32,
+        load: LoadOp,
+        c: impl for<'a> FnOnce(OpenGlRenderer<'a>),
+    ) -> GlStatistics {
+        self.render_viewport(
+            width,
+            height,
+            Bounds::saturating(0, 0, width as i32, height as i32),
+            load,
+            c,
+        )
+    }
+
+    /// Renders a pass into a sub-region of a `width`x`height` framebuffer. Shaders see positions
+    /// and resolution relative to the viewport, and clearing is restricted to it.
+    /// An empty viewport renders nothing, but still runs `c`.
+    ///
+    /// # Safety
+    /// The OpenGL context this was created with must be current on the calling thread.
+    pub unsafe fn render_viewport(
+        &mut self,
+        width: u32,
+        height: u32,
+        viewport: Bounds,
+        load: LoadOp,
+        c: impl for<'a> FnOnce(OpenGlRenderer<'a>),
+    ) -> GlStatistics {
+        // quad bounds are stored as u16, anything past that would be silently clipped
+        assert!(
+            width <= u16::MAX as u32 && height <= u16::MAX as u32,
+            "render target is too large ({}x{}), the maximum is 65535x65535",
+            width,
+            height
+        );
+
+        assert!(
+            viewport.right as u32 <= width && viewport.bottom as u32 <= height,
+            "viewport {:?} is outside of the {}x{} framebuffer",
+            viewport,
+            width,
+            height
+        );
+
+        assert!(
+            viewport.left <= viewport.right && viewport.top <= viewport.bottom,
+            "viewport {:?} is inverted",
+            viewport
+        );
+
         GlContext::within(&self.bindings, |context| {
-            self.data.begin_pass(width, height);
+            self.data.begin_pass(
+                (viewport.right - viewport.left) as u32,
+                (viewport.bottom - viewport.top) as u32,
+                // gl window coordinates have the origin in the bottom left corner
+                (viewport.left as u32, height - viewport.bottom as u32),
+                load,
+            );
             c(OpenGlRenderer {
                 data: &mut self.data,
+                gl: context,
             });
             self.data.end_pass(context)
         })
     }
 
+    /// Registers a shader ahead of time, outside of a render pass.
+    /// It gets compiled at the start of the next pass, or by [`compile`](Self::compile).
+    pub fn register<T: Shader>(&mut self) -> Result<(), GraphError> {
+        self.data.shaders.register::<T>()
+    }
+
+    /// Compiles the registered shaders now instead of at the start of the next pass, reporting
+    /// textures that don't fit into the atlas. A pass that has to compile reports that error in
+    /// [`GlStatistics::atlas_overflow`] instead, and doesn't draw anything until it is resolved.
+    ///
+    /// # Safety
+    /// The OpenGL context this was created with must be current on the calling thread.
+    pub unsafe fn compile(&mut self) -> Result<(), AtlasOverflow> {
+        GlContext::within(&self.bindings, |gl| {
+            if self.data.shaders.is_dirty() || self.data.program.is_none() {
+                self.data.compile(gl)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Renders the graph of a registered shader in Graphviz DOT format, for debugging.
+    pub fn shader_graph_dot<T: Shader>(&self) -> Option<String> {
+        self.data.shaders.to_dot::<T>()
+    }
+
+    /// Data fields of a registered shader (indices in read order) that are uploaded with
+    /// every quad but never affect the output. Those can be dropped from the `ShaderData`.
+    pub fn shader_unused_inputs<T: Shader>(&self) -> Option<Vec<usize>> {
+        self.data.shaders.unused_inputs::<T>()
+    }
+
+    /// Registered shaders that exceeded the size limits in [`OpenGlConfig`], in registration order.
+    pub fn shader_warnings(&self) -> &[ShaderSizeWarning] {
+        self.data.shaders.warnings()
+    }
+
+    /// Texture atlas usage as of the last program compilation, `None` if nothing was compiled yet.
+    pub fn atlas_statistics(&self) -> Option<&GlAtlasStatistics> {
+        self.data.atlas_stats.as_ref()
+    }
+
+    pub fn info(&self) -> &GlInfo {
+        &self.data.info
+    }
+
+    /// Deletes all the gl objects created by this renderer.
+    ///
+    /// # Safety
+    /// The OpenGL context this was created with must be current on the calling thread.
     pub unsafe fn delete(self) {
         GlContext::within(&self.bindings, |gl| {
             self.data.delete(gl);
@@ -98,15 +295,54 @@ impl OpenGl {
 impl<'a> OpenGlRenderer<'a> {
     pub fn reborrow(&mut self) -> OpenGlRenderer<'_> {
         OpenGlRenderer {
-            data: &mut self.data,
+            data: self.data,
+            gl: self.gl,
         }
     }
 
-    pub fn register<T: Shader>(&mut self) {
-        self.data.shaders.register::<T>();
+    /// Multiplies the output alpha of all the following draws in this pass by `opacity`.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.data.pass_encoding.opacity = (opacity.clamp(0.0, 1.0) * 65535.0).round() as u16;
+    }
+
+    /// Submits all the quads drawn so far to the GPU without ending the current pass.
+    ///
+    /// Quads are kept on the CPU until the next flush, so calling this periodically while drawing
+    /// huge scenes bounds the memory used by the pass.
+    pub fn flush(&mut self) {
+        self.data.flush(self.gl);
+    }
+
+    pub fn register<T: Shader>(&mut self) -> Result<(), GraphError> {
+        self.data.shaders.register::<T>()
+    }
+
+    /// Clears a region to a color (straight rgba), on top of everything drawn so far.
+    /// This flushes the pending quads.
+    pub fn clear(&mut self, bounds: impl Into<Bounds>, color: [f32; 4]) {
+        self.data.clear(self.gl, bounds.into(), color);
+    }
+
+    /// Reads back a region of the render target, with the origin in the top left corner like
+    /// [`Bounds`]. The region is clipped to the target. This flushes the pending quads.
+    pub fn screenshot(&mut self, bounds: impl Into<Bounds>) -> RgbaImage {
+        self.data.screenshot(self.gl, bounds.into())
+    }
+
+    /// Preallocates space for `quads` more draws of `T` in this pass.
+    pub fn reserve<T: Shader>(&mut self, quads: usize) {
+        self.data
+            .shaders
+            .reserve::<T>(&mut self.data.pass_encoding, quads);
     }
 
     pub fn draw<T: Shader>(&mut self, drawable: &T, bounds: impl Into<Bounds>) {
+        self.draw_z(drawable, bounds, 0.0);
+    }
+
+    /// Draws a quad at a given depth. Quads are stably sorted by depth (lowest first) before they
+    /// are submitted, but never across a [`flush`](Self::flush).
+    pub fn draw_z<T: Shader>(&mut self, drawable: &T, bounds: impl Into<Bounds>, z: f32) {
         let pass = self
             .data
             .pass_viewport
@@ -116,6 +352,7 @@ impl<'a> OpenGlRenderer<'a> {
         self.data.shaders.write(
             &mut self.data.pass_encoding,
             bounds.into(),
+            z,
             drawable,
             pass.width,
             pass.height,
@@ -124,49 +361,215 @@ impl<'a> OpenGlRenderer<'a> {
 }
 
 impl GlData {
-    fn begin_pass(&mut self, width: u32, height: u32) {
-        self.pass_viewport = Some(CurrentPass { width, height });
+    fn begin_pass(&mut self, width: u32, height: u32, origin: (u32, u32), load: LoadOp) {
+        self.pass_encoding.opacity = u16::MAX;
+        self.pass_viewport = Some(CurrentPass {
+            width,
+            height,
+            origin,
+            load,
+            started: false,
+            stats: GlStatistics {
+                gpu_time_msec: 0.0,
+                size_bytes: 0,
+                area_pixels: 0,
+                quads: 0,
+                quads_culled: 0,
+                drawcalls: 0,
+                atlas_overflow: None,
+            },
+        });
     }
 
     fn end_pass(&mut self, gl: GlContext) -> GlStatistics {
-        clear_error(gl);
+        self.flush(gl);
 
-        if self.shaders.is_dirty() || self.program.is_none() {
-            let (fragment_src, atlas) = self.shaders.recompile(self.info.max_texture_size as u32);
+        if let Some(time) = self.query.as_ref().and_then(|query| query.end(gl)) {
+            self.gpu_time = time;
+        }
 
-            if let Some(program) = self.program.take() {
-                program.program.delete(gl);
-                program.atlas.delete(gl);
-            }
+        check_error(gl);
 
-            let program = GlProgram::new(gl, codegen::VERTEX_SHADER, &fragment_src);
-            program.bind(gl);
+        let mut stats = self.pass_viewport.take().unwrap().stats;
+        stats.gpu_time_msec = (self.gpu_time as f64 / 1e6) as f32;
+        stats
+    }
 
-            uniform_1i(
-                gl,
-                program.get_uniform_loc(gl, "uBuffer"),
-                0, //texture location 0
-            );
+    fn compile(&mut self, gl: GlContext) -> Result<(), AtlasOverflow> {
+        let (fragment_src, atlas) = self.shaders.recompile(
+            self.info.max_texture_size as u32,
+            self.config.texture_sampling,
+        )?;
+
+        if let Some(program) = self.program.take() {
+            program.program.delete(gl);
+            program.atlas.delete(gl);
+        }
+
+        let program = GlProgram::new(gl, codegen::VERTEX_SHADER, &fragment_src);
+        program.bind(gl);
+
+        uniform_1i(
+            gl,
+            program.get_uniform_loc(gl, "uBuffer"),
+            0, //texture location 0
+        );
+
+        uniform_1i(
+            gl,
+            program.get_uniform_loc(gl, "uAtlas"),
+            1, //texture location 0
+        );
+
+        self.atlas_stats = Some(GlAtlasStatistics {
+            size: atlas.size,
+            area_used: atlas.area_used(),
+            shader_textures: self.shaders.texture_counts(),
+        });
+
+        let atlas_tex = atlas.create_image_rgba();
+        let atlas = GlTexture::new(gl, atlas.size, atlas.size, atlas_tex.as_raw());
 
-            uniform_1i(
+        self.program = Some(GlProgramData {
+            uni_buffer_offset_instance: program.get_uniform_loc(gl, "uBufferOffsetInstance"),
+            uni_buffer_offset_data: program.get_uniform_loc(gl, "uBufferOffsetData"),
+            uni_resolution: program.get_uniform_loc(gl, "uResolution"),
+            program,
+            atlas,
+        });
+
+        Ok(())
+    }
+
+    fn clear(&mut self, gl: GlContext, bounds: Bounds, color: [f32; 4]) {
+        self.flush(gl);
+
+        let pass = self
+            .pass_viewport
+            .as_ref()
+            .expect("call begin_pass() first");
+
+        let target = Bounds {
+            top: 0,
+            left: 0,
+            bottom: pass.height as u16,
+            right: pass.width as u16,
+        };
+
+        if let Some(bounds) = bounds.intersect(target) {
+            clear_color_rect(
                 gl,
-                program.get_uniform_loc(gl, "uAtlas"),
-                1, //texture location 0
+                (pass.origin.0 + bounds.left as u32) as i32,
+                (pass.origin.1 + pass.height - bounds.bottom as u32) as i32,
+                (bounds.right - bounds.left) as u32,
+                (bounds.bottom - bounds.top) as u32,
+                color,
             );
+        }
+    }
 
-            let atlas_tex = atlas.create_image_rgba();
-            let atlas = GlTexture::new(gl, atlas.size, atlas.size, &atlas_tex.as_raw());
+    fn screenshot(&mut self, gl: GlContext, bounds: Bounds) -> RgbaImage {
+        self.flush(gl);
 
-            self.program = Some(GlProgramData {
-                uni_buffer_offset_instance: program.get_uniform_loc(gl, "uBufferOffsetInstance"),
-                uni_buffer_offset_data: program.get_uniform_loc(gl, "uBufferOffsetData"),
-                uni_resolution: program.get_uniform_loc(gl, "uResolution"),
-                program,
-                atlas,
-            });
+        let pass = self
+            .pass_viewport
+            .as_ref()
+            .expect("call begin_pass() first");
+
+        let target = Bounds {
+            top: 0,
+            left: 0,
+            bottom: pass.height as u16,
+            right: pass.width as u16,
+        };
+
+        let bounds = match bounds.intersect(target) {
+            Some(bounds) => bounds,
+            None => return RgbaImage::new(0, 0),
+        };
+
+        let (width, height) = (
+            (bounds.right - bounds.left) as u32,
+            (bounds.bottom - bounds.top) as u32,
+        );
+
+        let pixels = read_pixels_rgba(
+            gl,
+            (pass.origin.0 + bounds.left as u32) as i32,
+            (pass.origin.1 + pass.height - bounds.bottom as u32) as i32,
+            width,
+            height,
+        );
+
+        // gl rows go bottom to top
+        let row = width as usize * 4;
+        let flipped = pixels.chunks_exact(row).rev().flatten().copied().collect();
+        RgbaImage::from_raw(width, height, flipped).unwrap()
+    }
+
+    fn flush(&mut self, gl: GlContext) {
+        clear_error(gl);
+
+        // with nothing to draw, only start the pass (clear and timer) and skip compiling the program
+        let empty = self.pass_encoding.quads.is_empty();
+        let mut overflow = None;
+        if !empty && (self.shaders.is_dirty() || self.program.is_none()) {
+            // the shaders stay dirty, so the next flush tries again
+            overflow = self.compile(gl).err();
+        }
+
+        let pass = self
+            .pass_viewport
+            .as_mut()
+            .expect("call begin_pass() first");
+
+        bind_default_framebuffer(gl);
+        viewport(
+            gl,
+            pass.origin.0 as i32,
+            pass.origin.1 as i32,
+            pass.width,
+            pass.height,
+        );
+
+        if self.config.srgb {
+            enable_framebuffer_srgb(gl);
+        } else {
+            disable_framebuffer_srgb(gl);
+        }
+
+        if !pass.started {
+            pass.started = true;
+            if let LoadOp::Clear(color) = pass.load {
+                clear_color_rect(
+                    gl,
+                    pass.origin.0 as i32,
+                    pass.origin.1 as i32,
+                    pass.width,
+                    pass.height,
+                    color,
+                );
+            }
+            if let Some(query) = &self.query {
+                query.begin(gl);
+            }
+        }
+
+        if overflow.is_some() {
+            // without a program that knows every shader there is nothing to draw the quads with
+            pass.stats.atlas_overflow = overflow;
+            self.pass_encoding.clear();
+            check_error(gl);
+            return;
+        }
+
+        if empty {
+            pass.stats.quads_culled += self.pass_encoding.culled;
+            self.pass_encoding.clear();
+            check_error(gl);
+            return;
         }
 
-        let pass = self.pass_viewport.as_ref().unwrap();
         let program_data = self.program.as_ref().unwrap();
 
         program_data.program.bind(gl);
@@ -175,96 +578,100 @@ impl GlData {
         self.vao.bind(gl);
         self.buffer.bind_texture(gl, 0);
 
-        bind_default_framebuffer(gl);
-        enable_blend_normal(gl);
-
-        viewport(gl, 0, 0, pass.width, pass.height);
         uniform_2f(
             gl,
             program_data.uni_resolution,
             [pass.width as f32, pass.height as f32],
         );
 
-        if self.config.srgb {
-            enable_framebuffer_srgb(gl);
-        } else {
-            disable_framebuffer_srgb(gl);
+        self.pass_encoding.sort_by_z();
+
+        let debug_groups = self.config.debug_groups && self.info.debug_groups;
+        if debug_groups {
+            push_debug_group(
+                gl,
+                &format!("picodraw pass ({} quads)", self.pass_encoding.quads.len()),
+            );
         }
 
-        clear_color(gl);
-
-        let mut stats_drawcalls = 0;
-        let mut stats_quads = 0;
-
-        self.gpu_time = self
-            .query
-            .time_elapsed(gl, || {
-                let mut quads = 0;
-                while quads < self.pass_encoding.quads.len() {
-                    let quads_start = quads;
-
-                    let (data_start, quad_data_start) = self.buffer.update(gl, |writer| {
-                        let data_start = writer.pointer();
-                        let local_data_start =
-                            self.pass_encoding.quads[quads_start].data_range.start;
-                        for quad in &self.pass_encoding.quads[quads_start..] {
-                            if writer.space_left()
-                                < quad.data_range.len() + 1 * (quads + 1 - quads_start)
-                            {
-                                break;
-                            }
-
-                            writer.write(&self.pass_encoding.data[quad.data_range.clone()]);
-                            quads += 1;
-                        }
-
-                        let quad_data_start = writer.pointer();
-                        if quads != quads_start {
-                            for quad in &self.pass_encoding.quads[quads_start..quads] {
-                                writer.write(&[[
-                                    (quad.bounds[0] as u32) | ((quad.bounds[1] as u32) << 16),
-                                    (quad.bounds[2] as u32) | ((quad.bounds[3] as u32) << 16),
-                                    quad.shader_id,
-                                    (quad.data_range.start - local_data_start) as u32,
-                                ]]);
-                            }
-                        } else {
-                            writer.mark_full();
-                        }
-
-                        (data_start, quad_data_start)
-                    });
-
-                    if quads != quads_start {
-                        stats_quads += (quads - quads_start) as u32;
-                        stats_drawcalls += 1;
-
-                        uniform_1i(
-                            gl,
-                            program_data.uni_buffer_offset_instance,
-                            quad_data_start as i32,
-                        );
-                        uniform_1i(gl, program_data.uni_buffer_offset_data, data_start as i32);
-                        draw_arrays_triangles(gl, (quads - quads_start) * 6);
+        let mut quads = 0;
+        while quads < self.pass_encoding.quads.len() {
+            let quads_start = quads;
+            // opaque and blended quads can't share a draw call
+            let opaque = self.pass_encoding.quads[quads_start].opaque;
+
+            let (data_start, quad_data_start) = self.buffer.update(gl, |writer| {
+                let data_start = writer.pointer();
+                let local_data_start = self.pass_encoding.quads[quads_start].data_range.start;
+                for quad in &self.pass_encoding.quads[quads_start..] {
+                    if writer.space_left() < quad.data_range.len() + (quads + 1 - quads_start) {
+                        break;
+                    }
+
+                    if quad.opaque != opaque {
+                        break;
                     }
+
+                    writer.write(&self.pass_encoding.data[quad.data_range.clone()]);
+                    quads += 1;
                 }
-            })
-            .unwrap_or(self.gpu_time);
+
+                let quad_data_start = writer.pointer();
+                if quads != quads_start {
+                    for quad in &self.pass_encoding.quads[quads_start..quads] {
+                        writer.write(&[[
+                            (quad.bounds[0] as u32) | ((quad.bounds[1] as u32) << 16),
+                            (quad.bounds[2] as u32) | ((quad.bounds[3] as u32) << 16),
+                            quad.shader_id | ((quad.opacity as u32) << 16),
+                            (quad.data_range.start - local_data_start) as u32,
+                        ]]);
+                    }
+                } else {
+                    // the buffer was empty and the quad still didn't fit, retrying would loop forever
+                    assert!(
+                        data_start != 0,
+                        "a single quad needs {} texels, more than the whole upload buffer",
+                        self.pass_encoding.quads[quads_start].data_range.len() + 1
+                    );
+
+                    writer.mark_full();
+                }
+
+                (data_start, quad_data_start)
+            });
+
+            if quads != quads_start {
+                pass.stats.quads += (quads - quads_start) as u32;
+                pass.stats.drawcalls += 1;
+
+                uniform_1i(
+                    gl,
+                    program_data.uni_buffer_offset_instance,
+                    quad_data_start as i32,
+                );
+                uniform_1i(gl, program_data.uni_buffer_offset_data, data_start as i32);
+
+                if opaque {
+                    disable_blend(gl);
+                } else {
+                    enable_blend_normal(gl);
+                }
+
+                draw_arrays_triangles(gl, (quads - quads_start) * 6);
+            }
+        }
+
+        if debug_groups {
+            pop_debug_group(gl);
+        }
 
         check_error(gl);
 
-        let stats = GlStatistics {
-            gpu_time_msec: (self.gpu_time as f64 / 1e6) as f32,
-            quads: stats_quads,
-            drawcalls: stats_drawcalls,
-            area_pixels: self.pass_encoding.total_area(),
-            size_bytes: (self.pass_encoding.size_texels() * size_of::<[u32; 4]>()) as u64,
-        };
+        pass.stats.quads_culled += self.pass_encoding.culled;
+        pass.stats.area_pixels += self.pass_encoding.total_area();
+        pass.stats.size_bytes += (self.pass_encoding.size_texels() * size_of::<[u32; 4]>()) as u64;
 
-        self.pass_viewport = None;
         self.pass_encoding.clear();
-
-        stats
     }
 
     fn new(gl: GlContext, config: OpenGlConfig) -> Self {
@@ -283,10 +690,11 @@ impl GlData {
             program: None,
             buffer: GlTextureBuffer::new(gl, info.max_texture_buffer_size.min(262144)),
             vao: GlVertexArrayObject::new(gl),
-            query: GlQuery::new(gl),
+            query: config.profiling.then(|| GlQuery::new(gl)),
             info,
 
-            shaders: ShaderMap::new(),
+            shaders: ShaderMap::new(config.max_shader_ops, config.max_shader_texture_samples),
+            atlas_stats: None,
 
             pass_encoding: QuadEncoder::new(),
             pass_viewport: None,
@@ -301,11 +709,17 @@ impl GlData {
 
         self.vao.delete(gl);
         self.buffer.delete(gl);
-        self.query.delete(gl);
+        if let Some(query) = self.query {
+            query.delete(gl);
+        }
     }
 }
 
 struct CurrentPass {
     width: u32,
     height: u32,
+    origin: (u32, u32),
+    load: LoadOp,
+    started: bool,
+    stats: GlStatistics,
 }
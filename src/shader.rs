@@ -1,5 +1,5 @@
-use crate::{Float2, Float4, ShaderData};
-use std::{any::TypeId, ops::Deref, u16};
+use crate::{Float, Float2, Float4, ShaderData};
+use std::{any::TypeId, ops::Deref};
 
 pub struct ShaderContext<'a, T> {
     pub vars: &'a T,
@@ -8,6 +8,8 @@ pub struct ShaderContext<'a, T> {
     pub bounds: Float4,
 }
 
+/// Pixel bounds of a quad. Coordinates are limited to `0..=65535`, use [`Bounds::try_new`]
+/// or [`Bounds::saturating`] to convert from wider coordinates.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Bounds {
     pub top: u16,
@@ -16,6 +18,16 @@ pub struct Bounds {
     pub right: u16,
 }
 
+/// How [`Bounds::from_f32_rect`] snaps fractional coordinates to pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rounding {
+    Round,
+    Floor,
+    Ceil,
+    /// Snap outwards, so that partially covered pixels are included.
+    Expand,
+}
+
 impl Bounds {
     pub fn infinite() -> Self {
         Self {
@@ -25,12 +37,119 @@ impl Bounds {
             right: u16::MAX,
         }
     }
+
+    /// Returns `None` if any of the coordinates doesn't fit in a `u16`.
+    pub fn try_new(left: i32, top: i32, right: i32, bottom: i32) -> Option<Self> {
+        Some(Self {
+            top: top.try_into().ok()?,
+            left: left.try_into().ok()?,
+            bottom: bottom.try_into().ok()?,
+            right: right.try_into().ok()?,
+        })
+    }
+
+    /// Clamps the coordinates to `0..=65535`.
+    pub fn saturating(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        let clamp = |x: i32| x.clamp(0, u16::MAX as i32) as u16;
+        Self {
+            top: clamp(top),
+            left: clamp(left),
+            bottom: clamp(bottom),
+            right: clamp(right),
+        }
+    }
+
+    /// Converts a floating point rectangle to pixel bounds, clamping it to `0..=65535`.
+    pub fn from_f32_rect(x: f32, y: f32, width: f32, height: f32, rounding: Rounding) -> Self {
+        let (left, top, right, bottom) = (x, y, x + width, y + height);
+        let (left, top, right, bottom) = match rounding {
+            Rounding::Round => (left.round(), top.round(), right.round(), bottom.round()),
+            Rounding::Floor => (left.floor(), top.floor(), right.floor(), bottom.floor()),
+            Rounding::Ceil => (left.ceil(), top.ceil(), right.ceil(), bottom.ceil()),
+            Rounding::Expand => (left.floor(), top.floor(), right.ceil(), bottom.ceil()),
+        };
+
+        Self::saturating(left as i32, top as i32, right as i32, bottom as i32)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.left >= self.right || self.top >= self.bottom
+    }
+
+    /// Checks if a point is inside the bounds. The right and bottom edges are exclusive.
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    pub fn intersect(&self, other: Bounds) -> Option<Bounds> {
+        let bounds = Bounds {
+            top: self.top.max(other.top),
+            left: self.left.max(other.left),
+            bottom: self.bottom.min(other.bottom),
+            right: self.right.min(other.right),
+        };
+
+        if bounds.is_empty() {
+            None
+        } else {
+            Some(bounds)
+        }
+    }
+
+    pub fn union(&self, other: Bounds) -> Bounds {
+        if self.is_empty() {
+            return other;
+        }
+
+        if other.is_empty() {
+            return *self;
+        }
+
+        Bounds {
+            top: self.top.min(other.top),
+            left: self.left.min(other.left),
+            bottom: self.bottom.max(other.bottom),
+            right: self.right.max(other.right),
+        }
+    }
+
+    pub fn clamp_to(&self, width: u16, height: u16) -> Bounds {
+        Bounds {
+            top: self.top.min(height),
+            left: self.left.min(width),
+            bottom: self.bottom.min(height),
+            right: self.right.min(width),
+        }
+    }
+}
+
+impl<'a, T> ShaderContext<'a, T> {
+    /// Position normalized to `[0, 1]` over the whole render target.
+    pub fn uv(&self) -> Float2 {
+        self.position / self.resolution
+    }
+
+    /// Width over height of the render target.
+    pub fn aspect_ratio(&self) -> Float {
+        self.resolution.x() / self.resolution.y()
+    }
+
+    /// Position in `[-1, 1]` over the render target height, centered on the middle of the target.
+    /// The x axis is scaled by the aspect ratio so that distances are uniform.
+    pub fn uv_centered(&self) -> Float2 {
+        (self.position * 2.0 - self.resolution) / self.resolution.y()
+    }
+
+    /// Position relative to the top left corner of the quad bounds.
+    pub fn position_local(&self) -> Float2 {
+        self.position - Float2::new(self.bounds.x(), self.bounds.y())
+    }
 }
 
 impl<'a, T> Deref for ShaderContext<'a, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        &self.vars
+        self.vars
     }
 }
 
@@ -43,10 +162,16 @@ pub trait Shader: ShaderData {
         id(|x| Self::draw(x))
     }
 
+    /// Set when the output alpha is always 1. Draws of opaque shaders skip blending
+    /// and overwrite whatever is below them, unless an opacity is set on the renderer.
+    const OPAQUE: bool = false;
+
     fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4;
 }
 
-impl<'a, T: Shader> Shader for &'a T {
+impl<T: Shader> Shader for &T {
+    const OPAQUE: bool = T::OPAQUE;
+
     fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
         T::draw(shader)
     }
@@ -55,3 +180,57 @@ impl<'a, T: Shader> Shader for &'a T {
         T::id()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(left: u16, top: u16, right: u16, bottom: u16) -> Bounds {
+        Bounds {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+
+    #[test]
+    fn intersect_union_contains() {
+        let a = bounds(0, 0, 10, 10);
+        let b = bounds(5, 5, 20, 20);
+
+        assert_eq!(a.intersect(b), Some(bounds(5, 5, 10, 10)));
+        assert_eq!(a.intersect(bounds(10, 0, 20, 10)), None);
+        assert_eq!(a.union(b), bounds(0, 0, 20, 20));
+        assert_eq!(a.union(bounds(3, 3, 3, 3)), a);
+
+        assert!(a.contains(0, 0));
+        assert!(a.contains(9, 9));
+        assert!(!a.contains(10, 5));
+    }
+
+    #[test]
+    fn checked_and_saturating() {
+        assert_eq!(Bounds::try_new(1, 2, 3, 4), Some(bounds(1, 2, 3, 4)));
+        assert_eq!(Bounds::try_new(-1, 2, 3, 4), None);
+        assert_eq!(Bounds::try_new(0, 0, 65536, 4), None);
+        assert_eq!(
+            Bounds::saturating(-5, 2, 70000, 4),
+            bounds(0, 2, u16::MAX, 4)
+        );
+    }
+
+    #[test]
+    fn from_f32_rect() {
+        let rect = |rounding| Bounds::from_f32_rect(1.4, 2.6, 3.0, 3.0, rounding);
+
+        assert_eq!(rect(Rounding::Round), bounds(1, 3, 4, 6));
+        assert_eq!(rect(Rounding::Floor), bounds(1, 2, 4, 5));
+        assert_eq!(rect(Rounding::Ceil), bounds(2, 3, 5, 6));
+        assert_eq!(rect(Rounding::Expand), bounds(1, 2, 5, 6));
+        assert_eq!(
+            Bounds::from_f32_rect(-10.0, 0.0, 1e6, 1.0, Rounding::Round),
+            bounds(0, 0, u16::MAX, 1)
+        );
+    }
+}
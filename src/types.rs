@@ -6,11 +6,14 @@ pub trait GlType: Copy + 'static {
     fn wrap(value: OpAddr) -> Self;
     fn unwrap(self) -> OpAddr;
 
+    #[track_caller]
     fn input_raw(id: usize) -> Self {
         Self::wrap(push_op(Op::Input(id), Self::TYPE))
     }
 }
 
+// `len` is the vector length, there is nothing to be empty
+#[allow(clippy::len_without_is_empty)]
 pub trait GlFloat:
     From<Float>
     + From<f32>
@@ -36,6 +39,8 @@ pub trait GlFloat:
     fn atan(self) -> Self;
 
     fn sqrt(self) -> Self;
+    /// Raises to the given power. Negative bases behave like [`f32::powf`], giving a real result
+    /// for integer powers and NaN otherwise. Literal powers 1 to 4 are expanded into multiplications.
     fn pow(self, power: impl Into<Self>) -> Self;
     fn exp(self) -> Self;
     fn ln(self) -> Self;
@@ -46,7 +51,17 @@ pub trait GlFloat:
     fn sign(self) -> Self;
     fn min(self, x: impl Into<Self>) -> Self;
     fn max(self, x: impl Into<Self>) -> Self;
+    /// Same as `self.min(max).max(min)`, so it returns `min` when `min > max`.
     fn clamp(self, min: impl Into<Self>, max: impl Into<Self>) -> Self;
+    fn saturate(self) -> Self;
+
+    /// Euclidean remainder, always in `[0, |rhs|)`. Note that `%` follows GLSL `mod`
+    /// and takes the sign of `rhs`.
+    fn rem_euclid(self, rhs: impl Into<Self>) -> Self;
+    /// Truncated remainder, takes the sign of `self` like C `fmod`.
+    fn fmod(self, rhs: impl Into<Self>) -> Self;
+    /// Posterizes into `steps` bands per unit, `floor(self * steps) / steps`.
+    fn quantize(self, steps: impl Into<Self>) -> Self;
 
     fn step(self, edge: impl Into<Self>) -> Self;
     fn smoothstep(self, min: impl Into<Self>, max: impl Into<Self>) -> Self;
@@ -66,40 +81,70 @@ pub trait GlFloat:
 pub struct Float(pub(crate) OpAddr);
 
 impl Float {
+    #[track_caller]
     pub fn atan2(self, x: impl Into<Self>) -> Self {
         Self(push_op(Op::Atan2(self.0, x.into().0), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn le(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Le(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn lt(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Lt(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn ge(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Ge(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn gt(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Gt(self.0, rhs.into().0), ValueType::Bool1))
     }
+
+    /// Blends between `a` (mask 0) and `b` (mask 1) using this value as a mask in `[0, 1]`.
+    ///
+    /// Same as `T::from(mask).lerp(a, b)`, but branch-free even for masks
+    /// computed arithmetically, unlike `select`.
+    #[track_caller]
+    pub fn select_mix<T: GlFloat + From<Float>>(self, a: impl Into<T>, b: impl Into<T>) -> T {
+        T::from(self).lerp(a.into(), b.into())
+    }
+
+    /// `sqrt(self² + other²)`.
+    #[track_caller]
+    pub fn hypot(self, other: impl Into<Self>) -> Self {
+        let other = other.into();
+        (self * self + other * other).sqrt()
+    }
+
+    /// Converts a float mask to a boolean, true when the mask is positive.
+    #[track_caller]
+    pub fn as_bool_gt0(self) -> Bool {
+        self.gt(0.0)
+    }
 }
 
 impl From<f32> for Float {
+    #[track_caller]
     fn from(value: f32) -> Self {
         Self(push_op(Op::LitFloat(value), ValueType::Float1))
     }
 }
 
 impl From<Int> for Float {
+    #[track_caller]
     fn from(value: Int) -> Self {
         Self(push_op(Op::CastFloat(value.0), ValueType::Float1))
     }
 }
 
 impl From<Bool> for Float {
+    #[track_caller]
     fn from(value: Bool) -> Self {
         Self(push_op(Op::CastFloat(value.0), ValueType::Float1))
     }
@@ -109,44 +154,83 @@ impl From<Bool> for Float {
 pub struct Int(pub(crate) OpAddr);
 
 impl Int {
+    #[track_caller]
     pub fn le(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Le(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn lt(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Lt(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn ge(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Ge(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn gt(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Gt(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn eq(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Eq(self.0, rhs.into().0), ValueType::Bool1))
     }
 
+    #[track_caller]
     pub fn neq(self, rhs: impl Into<Self>) -> Bool {
         Bool(push_op(Op::Ne(self.0, rhs.into().0), ValueType::Bool1))
     }
+
+    // comparing as unsigned flips the signed result whenever the operand signs differ
+    #[track_caller]
+    pub fn lt_unsigned(self, rhs: impl Into<Self>) -> Bool {
+        let rhs = rhs.into();
+        self.lt(rhs) ^ (self.lt(0) ^ rhs.lt(0))
+    }
+
+    #[track_caller]
+    pub fn le_unsigned(self, rhs: impl Into<Self>) -> Bool {
+        let rhs = rhs.into();
+        self.le(rhs) ^ (self.lt(0) ^ rhs.lt(0))
+    }
+
+    #[track_caller]
+    pub fn gt_unsigned(self, rhs: impl Into<Self>) -> Bool {
+        let rhs = rhs.into();
+        self.gt(rhs) ^ (self.lt(0) ^ rhs.lt(0))
+    }
+
+    #[track_caller]
+    pub fn ge_unsigned(self, rhs: impl Into<Self>) -> Bool {
+        let rhs = rhs.into();
+        self.ge(rhs) ^ (self.lt(0) ^ rhs.lt(0))
+    }
+
+    #[track_caller]
+    pub fn to_float_unsigned(self) -> Float {
+        Float::from(self) + Float::from(4294967296.0).select(0.0, self.lt(0))
+    }
 }
 
 impl From<i32> for Int {
+    #[track_caller]
     fn from(value: i32) -> Self {
         Self(push_op(Op::LitInt(value), ValueType::Int1))
     }
 }
 
 impl From<Bool> for Int {
+    #[track_caller]
     fn from(value: Bool) -> Self {
         Self(push_op(Op::CastInt(value.0), ValueType::Int1))
     }
 }
 
 impl From<Float> for Int {
+    #[track_caller]
     fn from(value: Float) -> Self {
         Self(push_op(Op::CastInt(value.0), ValueType::Int1))
     }
@@ -159,6 +243,15 @@ pub struct Bool(pub(crate) OpAddr);
 pub struct Float2(pub(crate) OpAddr);
 
 impl Float2 {
+    /// [`len`](GlFloat::len) that stays finite for huge components, by scaling by the largest one first.
+    #[track_caller]
+    pub fn length_safe(self) -> Float {
+        let abs = self.abs();
+        let scale = abs.x().max(abs.y());
+        (self / scale.max(f32::MIN_POSITIVE)).len() * scale
+    }
+
+    #[track_caller]
     pub fn new(x: impl Into<Float>, y: impl Into<Float>) -> Self {
         Self(push_op(
             Op::NewVec2(x.into().0, y.into().0),
@@ -166,22 +259,49 @@ impl Float2 {
         ))
     }
 
+    #[track_caller]
     pub fn x(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::X), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn y(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::Y), ValueType::Float1))
     }
+
+    #[track_caller]
+    pub fn with_x(self, x: impl Into<Float>) -> Self {
+        Self::new(x, self.y())
+    }
+
+    #[track_caller]
+    pub fn with_y(self, y: impl Into<Float>) -> Self {
+        Self::new(self.x(), y)
+    }
+
+    /// Returns `(angle, radius)`, the angle is in `[-pi, pi]` measured from the positive x axis.
+    #[track_caller]
+    pub fn to_polar(self) -> (Float, Float) {
+        (self.y().atan2(self.x()), self.len())
+    }
+
+    #[track_caller]
+    pub fn from_polar(angle: impl Into<Float>, radius: impl Into<Float>) -> Self {
+        let angle = angle.into();
+        let radius = radius.into();
+        Self::new(angle.cos() * radius, angle.sin() * radius)
+    }
 }
 
 impl From<Float> for Float2 {
+    #[track_caller]
     fn from(value: Float) -> Self {
         Self(push_op(Op::SplatVec2(value.0), ValueType::Float2))
     }
 }
 
 impl From<f32> for Float2 {
+    #[track_caller]
     fn from(value: f32) -> Self {
         Self::from(Float::from(value))
     }
@@ -191,6 +311,15 @@ impl From<f32> for Float2 {
 pub struct Float3(pub(crate) OpAddr);
 
 impl Float3 {
+    /// Overflow-safe length, see [`Float2::length_safe`].
+    #[track_caller]
+    pub fn length_safe(self) -> Float {
+        let abs = self.abs();
+        let scale = abs.x().max(abs.y()).max(abs.z());
+        (self / scale.max(f32::MIN_POSITIVE)).len() * scale
+    }
+
+    #[track_caller]
     pub fn new(x: impl Into<Float>, y: impl Into<Float>, z: impl Into<Float>) -> Self {
         Self(push_op(
             Op::NewVec3(x.into().0, y.into().0, z.into().0),
@@ -198,30 +327,51 @@ impl Float3 {
         ))
     }
 
+    #[track_caller]
     pub fn x(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::X), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn y(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::Y), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn z(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::Z), ValueType::Float1))
     }
 
+    #[track_caller]
+    pub fn with_x(self, x: impl Into<Float>) -> Self {
+        Self::new(x, self.y(), self.z())
+    }
+
+    #[track_caller]
+    pub fn with_y(self, y: impl Into<Float>) -> Self {
+        Self::new(self.x(), y, self.z())
+    }
+
+    #[track_caller]
+    pub fn with_z(self, z: impl Into<Float>) -> Self {
+        Self::new(self.x(), self.y(), z)
+    }
+
+    #[track_caller]
     pub fn cross(self, rhs: impl Into<Self>) -> Self {
         Self(push_op(Op::Cross(self.0, rhs.into().0), ValueType::Float3))
     }
 }
 
 impl From<Float> for Float3 {
+    #[track_caller]
     fn from(value: Float) -> Self {
         Self(push_op(Op::SplatVec3(value.0), ValueType::Float3))
     }
 }
 
 impl From<f32> for Float3 {
+    #[track_caller]
     fn from(value: f32) -> Self {
         Self::from(Float::from(value))
     }
@@ -231,6 +381,15 @@ impl From<f32> for Float3 {
 pub struct Float4(pub(crate) OpAddr);
 
 impl Float4 {
+    /// Overflow-safe length, see [`Float2::length_safe`].
+    #[track_caller]
+    pub fn length_safe(self) -> Float {
+        let abs = self.abs();
+        let scale = abs.x().max(abs.y()).max(abs.z()).max(abs.w());
+        (self / scale.max(f32::MIN_POSITIVE)).len() * scale
+    }
+
+    #[track_caller]
     pub fn new(
         x: impl Into<Float>,
         y: impl Into<Float>,
@@ -243,30 +402,71 @@ impl Float4 {
         ))
     }
 
+    #[track_caller]
     pub fn x(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::X), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn y(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::Y), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn z(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::Z), ValueType::Float1))
     }
 
+    #[track_caller]
     pub fn w(self) -> Float {
         Float(push_op(Op::Swizzle1(self.0, Swizzle::W), ValueType::Float1))
     }
+
+    #[track_caller]
+    pub fn with_x(self, x: impl Into<Float>) -> Self {
+        Self::new(x, self.y(), self.z(), self.w())
+    }
+
+    #[track_caller]
+    pub fn with_y(self, y: impl Into<Float>) -> Self {
+        Self::new(self.x(), y, self.z(), self.w())
+    }
+
+    #[track_caller]
+    pub fn with_z(self, z: impl Into<Float>) -> Self {
+        Self::new(self.x(), self.y(), z, self.w())
+    }
+
+    #[track_caller]
+    pub fn with_w(self, w: impl Into<Float>) -> Self {
+        Self::new(self.x(), self.y(), self.z(), w)
+    }
+
+    /// Multiplies the rgb channels by alpha.
+    #[track_caller]
+    pub fn premultiply(self) -> Self {
+        let a = self.w();
+        Self::new(self.x() * a, self.y() * a, self.z() * a, a)
+    }
+
+    /// Divides the rgb channels by alpha. Fully transparent colors become transparent black.
+    #[track_caller]
+    pub fn unpremultiply(self) -> Self {
+        let a = self.w();
+        let inv = (1.0 / a).select(0.0, a.gt(0.0));
+        Self::new(self.x() * inv, self.y() * inv, self.z() * inv, a)
+    }
 }
 
 impl From<Float> for Float4 {
+    #[track_caller]
     fn from(value: Float) -> Self {
         Self(push_op(Op::SplatVec4(value.0), ValueType::Float4))
     }
 }
 
 impl From<f32> for Float4 {
+    #[track_caller]
     fn from(value: f32) -> Self {
         Self::from(Float::from(value))
     }
@@ -278,16 +478,22 @@ pub struct Texture(OpAddr);
 impl GlType for Texture {
     const TYPE: ValueType = ValueType::Texture;
 
+    #[track_caller]
     fn wrap(value: OpAddr) -> Self {
         Self(value)
     }
 
+    #[track_caller]
     fn unwrap(self) -> OpAddr {
         self.0
     }
 }
 
 impl Texture {
+    /// Bilinear sample at a position in texels. Which positions hit texel centers depends on
+    /// [`OpenGlConfig::texture_sampling`](crate::opengl::OpenGlConfig::texture_sampling),
+    /// by default texel `i` is centered at `i`. Positions outside the texture are clamped to the edge.
+    #[track_caller]
     pub fn linear(&self, pos: impl Into<Float2>) -> Float4 {
         Float4(push_op(
             Op::TextureSampleLinear(self.0, pos.into().0),
@@ -295,6 +501,8 @@ impl Texture {
         ))
     }
 
+    /// Reads the texel containing a position, using the same convention as [`linear`](Self::linear).
+    #[track_caller]
     pub fn nearest(&self, pos: impl Into<Float2>) -> Float4 {
         Float4(push_op(
             Op::TextureSampleNearest(self.0, pos.into().0),
@@ -302,9 +510,30 @@ impl Texture {
         ))
     }
 
+    #[track_caller]
     pub fn size(&self) -> Float2 {
         Float2(push_op(Op::TextureSize(self.0), ValueType::Float2))
     }
+
+    /// Width in texels. Texture sizes are compile time constants well below 2^24,
+    /// so this is exact.
+    #[track_caller]
+    pub fn width(&self) -> Int {
+        Int::from(self.size().x())
+    }
+
+    /// Height in texels, see [`width`](Self::width).
+    #[track_caller]
+    pub fn height(&self) -> Int {
+        Int::from(self.size().y())
+    }
+
+    /// Reads a single texel by integer coordinate, without filtering.
+    /// Coordinates outside the texture are clamped to the edge.
+    #[track_caller]
+    pub fn fetch(&self, x: impl Into<Int>, y: impl Into<Int>) -> Float4 {
+        self.nearest(Float2::new(Float::from(x.into()), Float::from(y.into())))
+    }
 }
 
 pub trait GlLoopVars: Sized {
@@ -315,80 +544,99 @@ macro_rules! impl_float {
     ($type:ty, $vtype:ident) => {
         impl GlType for $type {
             const TYPE: ValueType = ValueType::$vtype;
+            #[track_caller]
             fn wrap(value: OpAddr) -> Self {
                 Self(value)
             }
 
+            #[track_caller]
             fn unwrap(self) -> OpAddr {
                 self.0
             }
         }
 
         impl GlFloat for $type {
+            #[track_caller]
             fn sin(self) -> Self {
                 Self(push_op(Op::Sin(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn cos(self) -> Self {
                 Self(push_op(Op::Cos(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn tan(self) -> Self {
                 Self(push_op(Op::Tan(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn asin(self) -> Self {
                 Self(push_op(Op::Asin(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn acos(self) -> Self {
                 Self(push_op(Op::Acos(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn atan(self) -> Self {
                 Self(push_op(Op::Atan(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn sqrt(self) -> Self {
                 Self(push_op(Op::Sqrt(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn pow(self, power: impl Into<Self>) -> Self {
                 Self(push_op(Op::Pow(self.0, power.into().0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn exp(self) -> Self {
                 Self(push_op(Op::Exp(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn ln(self) -> Self {
                 Self(push_op(Op::Ln(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn floor(self) -> Self {
                 Self(push_op(Op::Floor(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn fract(self) -> Self {
                 Self(push_op(Op::Fract(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn abs(self) -> Self {
                 Self(push_op(Op::Abs(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn sign(self) -> Self {
                 Self(push_op(Op::Sign(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn min(self, x: impl Into<Self>) -> Self {
                 Self(push_op(Op::Min(self.0, x.into().0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn max(self, x: impl Into<Self>) -> Self {
                 Self(push_op(Op::Max(self.0, x.into().0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn clamp(self, min: impl Into<Self>, max: impl Into<Self>) -> Self {
                 Self(push_op(
                     Op::Clamp(self.0, min.into().0, max.into().0),
@@ -396,10 +644,36 @@ macro_rules! impl_float {
                 ))
             }
 
+            #[track_caller]
+            fn saturate(self) -> Self {
+                self.clamp(0.0, 1.0)
+            }
+
+            #[track_caller]
+            fn rem_euclid(self, rhs: impl Into<Self>) -> Self {
+                let rhs = rhs.into().abs();
+                self - rhs * (self / rhs).floor()
+            }
+
+            #[track_caller]
+            fn fmod(self, rhs: impl Into<Self>) -> Self {
+                let rhs = rhs.into();
+                let q = self / rhs;
+                self - rhs * (q.sign() * q.abs().floor())
+            }
+
+            #[track_caller]
+            fn quantize(self, steps: impl Into<Self>) -> Self {
+                let steps = steps.into();
+                (self * steps).floor() / steps
+            }
+
+            #[track_caller]
             fn step(self, edge: impl Into<Self>) -> Self {
                 Self(push_op(Op::Step(self.0, edge.into().0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn smoothstep(self, min: impl Into<Self>, max: impl Into<Self>) -> Self {
                 Self(push_op(
                     Op::Smoothstep(self.0, min.into().0, max.into().0),
@@ -407,6 +681,7 @@ macro_rules! impl_float {
                 ))
             }
 
+            #[track_caller]
             fn lerp(self, min: impl Into<Self>, max: impl Into<Self>) -> Self {
                 Self(push_op(
                     Op::Lerp(self.0, min.into().0, max.into().0),
@@ -414,6 +689,7 @@ macro_rules! impl_float {
                 ))
             }
 
+            #[track_caller]
             fn select(self, other: impl Into<Self>, cond: impl Into<Bool>) -> Self {
                 Self(push_op(
                     Op::Select(cond.into().0, self.0, other.into().0),
@@ -421,6 +697,7 @@ macro_rules! impl_float {
                 ))
             }
 
+            #[track_caller]
             fn norm(self) -> Self {
                 if ValueType::$vtype == ValueType::Float1 {
                     Self(push_op(Op::Sign(self.0), ValueType::Float1))
@@ -429,6 +706,7 @@ macro_rules! impl_float {
                 }
             }
 
+            #[track_caller]
             fn len(self) -> Float {
                 if ValueType::$vtype == ValueType::Float1 {
                     Float(push_op(Op::Abs(self.0), ValueType::Float1))
@@ -437,6 +715,7 @@ macro_rules! impl_float {
                 }
             }
 
+            #[track_caller]
             fn dot(self, rhs: impl Into<Self>) -> Float {
                 if ValueType::$vtype == ValueType::Float1 {
                     Float(push_op(Op::Mul(self.0, rhs.into().0), ValueType::Float1))
@@ -445,21 +724,25 @@ macro_rules! impl_float {
                 }
             }
 
+            #[track_caller]
             fn dfdx(self) -> Self {
-                Self(push_op(Op::DerivX(self.0), ValueType::Float1))
+                Self(push_op(Op::DerivX(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn dfdy(self) -> Self {
-                Self(push_op(Op::DerivY(self.0), ValueType::Float1))
+                Self(push_op(Op::DerivY(self.0), ValueType::$vtype))
             }
 
+            #[track_caller]
             fn fwidth(self) -> Self {
-                Self(push_op(Op::DerivWidth(self.0), ValueType::Float1))
+                Self(push_op(Op::DerivWidth(self.0), ValueType::$vtype))
             }
         }
 
         impl Add<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn add(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Add(self.0, rhs.0), ValueType::$vtype))
             }
@@ -467,6 +750,7 @@ macro_rules! impl_float {
 
         impl Sub<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn sub(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Sub(self.0, rhs.0), ValueType::$vtype))
             }
@@ -474,6 +758,7 @@ macro_rules! impl_float {
 
         impl Mul<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn mul(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Mul(self.0, rhs.0), ValueType::$vtype))
             }
@@ -481,6 +766,7 @@ macro_rules! impl_float {
 
         impl Div<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn div(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Div(self.0, rhs.0), ValueType::$vtype))
             }
@@ -488,6 +774,7 @@ macro_rules! impl_float {
 
         impl Rem<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn rem(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Rem(self.0, rhs.0), ValueType::$vtype))
             }
@@ -495,6 +782,7 @@ macro_rules! impl_float {
 
         impl Neg for $type {
             type Output = $type;
+            #[track_caller]
             fn neg(self) -> Self::Output {
                 Self(push_op(Op::Neg(self.0), ValueType::$vtype))
             }
@@ -502,6 +790,7 @@ macro_rules! impl_float {
 
         impl Add<f32> for $type {
             type Output = Self;
+            #[track_caller]
             fn add(self, rhs: f32) -> Self::Output {
                 self + <$type>::from(rhs)
             }
@@ -509,6 +798,7 @@ macro_rules! impl_float {
 
         impl Sub<f32> for $type {
             type Output = Self;
+            #[track_caller]
             fn sub(self, rhs: f32) -> Self::Output {
                 self - <$type>::from(rhs)
             }
@@ -516,6 +806,7 @@ macro_rules! impl_float {
 
         impl Mul<f32> for $type {
             type Output = Self;
+            #[track_caller]
             fn mul(self, rhs: f32) -> Self::Output {
                 self * <$type>::from(rhs)
             }
@@ -523,6 +814,7 @@ macro_rules! impl_float {
 
         impl Div<f32> for $type {
             type Output = Self;
+            #[track_caller]
             fn div(self, rhs: f32) -> Self::Output {
                 self / <$type>::from(rhs)
             }
@@ -530,6 +822,7 @@ macro_rules! impl_float {
 
         impl Rem<f32> for $type {
             type Output = Self;
+            #[track_caller]
             fn rem(self, rhs: f32) -> Self::Output {
                 self % <$type>::from(rhs)
             }
@@ -537,6 +830,7 @@ macro_rules! impl_float {
 
         impl Add<$type> for f32 {
             type Output = $type;
+            #[track_caller]
             fn add(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) + rhs
             }
@@ -544,6 +838,7 @@ macro_rules! impl_float {
 
         impl Sub<$type> for f32 {
             type Output = $type;
+            #[track_caller]
             fn sub(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) - rhs
             }
@@ -551,6 +846,7 @@ macro_rules! impl_float {
 
         impl Mul<$type> for f32 {
             type Output = $type;
+            #[track_caller]
             fn mul(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) * rhs
             }
@@ -558,6 +854,7 @@ macro_rules! impl_float {
 
         impl Div<$type> for f32 {
             type Output = $type;
+            #[track_caller]
             fn div(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) / rhs
             }
@@ -565,6 +862,7 @@ macro_rules! impl_float {
 
         impl Rem<$type> for f32 {
             type Output = $type;
+            #[track_caller]
             fn rem(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) % rhs
             }
@@ -576,34 +874,58 @@ macro_rules! impl_int {
     ($type:ty, $vtype:ident) => {
         impl GlType for $type {
             const TYPE: ValueType = ValueType::$vtype;
+            #[track_caller]
             fn wrap(value: OpAddr) -> Self {
                 Self(value)
             }
 
+            #[track_caller]
             fn unwrap(self) -> OpAddr {
                 self.0
             }
         }
 
         impl $type {
+            #[track_caller]
             pub fn min(self, x: impl Into<Self>) -> Self {
                 Self(push_op(Op::Min(self.0, x.into().0), ValueType::$vtype))
             }
 
+            #[track_caller]
             pub fn max(self, x: impl Into<Self>) -> Self {
                 Self(push_op(Op::Max(self.0, x.into().0), ValueType::$vtype))
             }
 
+            /// Same as `self.min(max).max(min)`, so it returns `min` when `min > max`.
+            #[track_caller]
             pub fn clamp(self, min: impl Into<Self>, max: impl Into<Self>) -> Self {
                 Self(push_op(
                     Op::Clamp(self.0, min.into().0, max.into().0),
                     ValueType::$vtype,
                 ))
             }
+
+            #[track_caller]
+            pub fn select(self, other: impl Into<Self>, cond: impl Into<Bool>) -> Self {
+                Self(push_op(
+                    Op::Select(cond.into().0, self.0, other.into().0),
+                    ValueType::$vtype,
+                ))
+            }
+
+            /// Wrapping absolute value, `abs(i32::MIN)` is `i32::MIN`.
+            ///
+            /// GLSL leaves `abs` of the minimum integer unspecified, while negation is defined to wrap,
+            /// so this is lowered to a negation instead.
+            #[track_caller]
+            pub fn abs(self) -> Self {
+                (-self).select(self, self.lt(0))
+            }
         }
 
         impl Add<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn add(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Add(self.0, rhs.0), ValueType::$vtype))
             }
@@ -611,6 +933,7 @@ macro_rules! impl_int {
 
         impl Sub<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn sub(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Sub(self.0, rhs.0), ValueType::$vtype))
             }
@@ -618,6 +941,7 @@ macro_rules! impl_int {
 
         impl Mul<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn mul(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Mul(self.0, rhs.0), ValueType::$vtype))
             }
@@ -625,6 +949,7 @@ macro_rules! impl_int {
 
         impl Div<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn div(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Div(self.0, rhs.0), ValueType::$vtype))
             }
@@ -632,6 +957,7 @@ macro_rules! impl_int {
 
         impl Rem<$type> for $type {
             type Output = $type;
+            #[track_caller]
             fn rem(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Rem(self.0, rhs.0), ValueType::$vtype))
             }
@@ -639,6 +965,7 @@ macro_rules! impl_int {
 
         impl Neg for $type {
             type Output = $type;
+            #[track_caller]
             fn neg(self) -> Self::Output {
                 Self(push_op(Op::Neg(self.0), ValueType::$vtype))
             }
@@ -646,6 +973,7 @@ macro_rules! impl_int {
 
         impl Add<i32> for $type {
             type Output = Self;
+            #[track_caller]
             fn add(self, rhs: i32) -> Self::Output {
                 self + <$type>::from(rhs)
             }
@@ -653,6 +981,7 @@ macro_rules! impl_int {
 
         impl Sub<i32> for $type {
             type Output = Self;
+            #[track_caller]
             fn sub(self, rhs: i32) -> Self::Output {
                 self - <$type>::from(rhs)
             }
@@ -660,6 +989,7 @@ macro_rules! impl_int {
 
         impl Mul<i32> for $type {
             type Output = Self;
+            #[track_caller]
             fn mul(self, rhs: i32) -> Self::Output {
                 self * <$type>::from(rhs)
             }
@@ -667,6 +997,7 @@ macro_rules! impl_int {
 
         impl Div<i32> for $type {
             type Output = Self;
+            #[track_caller]
             fn div(self, rhs: i32) -> Self::Output {
                 self / <$type>::from(rhs)
             }
@@ -674,6 +1005,7 @@ macro_rules! impl_int {
 
         impl Rem<i32> for $type {
             type Output = Self;
+            #[track_caller]
             fn rem(self, rhs: i32) -> Self::Output {
                 self % <$type>::from(rhs)
             }
@@ -681,6 +1013,7 @@ macro_rules! impl_int {
 
         impl Add<$type> for i32 {
             type Output = $type;
+            #[track_caller]
             fn add(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) + rhs
             }
@@ -688,6 +1021,7 @@ macro_rules! impl_int {
 
         impl Sub<$type> for i32 {
             type Output = $type;
+            #[track_caller]
             fn sub(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) - rhs
             }
@@ -695,6 +1029,7 @@ macro_rules! impl_int {
 
         impl Mul<$type> for i32 {
             type Output = $type;
+            #[track_caller]
             fn mul(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) * rhs
             }
@@ -702,6 +1037,7 @@ macro_rules! impl_int {
 
         impl Div<$type> for i32 {
             type Output = $type;
+            #[track_caller]
             fn div(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) / rhs
             }
@@ -709,6 +1045,7 @@ macro_rules! impl_int {
 
         impl Rem<$type> for i32 {
             type Output = $type;
+            #[track_caller]
             fn rem(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) % rhs
             }
@@ -720,16 +1057,19 @@ macro_rules! impl_bool {
     ($type:ty, $vtype:ident) => {
         impl GlType for $type {
             const TYPE: ValueType = ValueType::$vtype;
+            #[track_caller]
             fn wrap(value: OpAddr) -> Self {
                 Self(value)
             }
 
+            #[track_caller]
             fn unwrap(self) -> OpAddr {
                 self.0
             }
         }
 
         impl From<bool> for $type {
+            #[track_caller]
             fn from(value: bool) -> Self {
                 Self(push_op(Op::LitBool(value), ValueType::$vtype))
             }
@@ -737,6 +1077,7 @@ macro_rules! impl_bool {
 
         impl BitAnd<$type> for $type {
             type Output = Self;
+            #[track_caller]
             fn bitand(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::And(self.0, rhs.0), ValueType::$vtype))
             }
@@ -744,6 +1085,7 @@ macro_rules! impl_bool {
 
         impl BitOr<$type> for $type {
             type Output = Self;
+            #[track_caller]
             fn bitor(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Or(self.0, rhs.0), ValueType::$vtype))
             }
@@ -751,6 +1093,7 @@ macro_rules! impl_bool {
 
         impl BitXor<$type> for $type {
             type Output = Self;
+            #[track_caller]
             fn bitxor(self, rhs: $type) -> Self::Output {
                 Self(push_op(Op::Xor(self.0, rhs.0), ValueType::$vtype))
             }
@@ -758,6 +1101,7 @@ macro_rules! impl_bool {
 
         impl Not for $type {
             type Output = Self;
+            #[track_caller]
             fn not(self) -> Self::Output {
                 Self(push_op(Op::Not(self.0), ValueType::$vtype))
             }
@@ -765,6 +1109,7 @@ macro_rules! impl_bool {
 
         impl BitAnd<bool> for $type {
             type Output = Self;
+            #[track_caller]
             fn bitand(self, rhs: bool) -> Self::Output {
                 if rhs {
                     self
@@ -776,6 +1121,7 @@ macro_rules! impl_bool {
 
         impl BitOr<bool> for $type {
             type Output = Self;
+            #[track_caller]
             fn bitor(self, rhs: bool) -> Self::Output {
                 if rhs {
                     Self::from(true)
@@ -787,6 +1133,7 @@ macro_rules! impl_bool {
 
         impl BitXor<bool> for $type {
             type Output = Self;
+            #[track_caller]
             fn bitxor(self, rhs: bool) -> Self::Output {
                 if rhs {
                     !self
@@ -804,6 +1151,7 @@ macro_rules! impl_float_vec {
 
         impl Add<Float> for $type {
             type Output = Self;
+            #[track_caller]
             fn add(self, rhs: Float) -> Self::Output {
                 self + <$type>::from(rhs)
             }
@@ -811,6 +1159,7 @@ macro_rules! impl_float_vec {
 
         impl Sub<Float> for $type {
             type Output = Self;
+            #[track_caller]
             fn sub(self, rhs: Float) -> Self::Output {
                 self - <$type>::from(rhs)
             }
@@ -818,6 +1167,7 @@ macro_rules! impl_float_vec {
 
         impl Mul<Float> for $type {
             type Output = Self;
+            #[track_caller]
             fn mul(self, rhs: Float) -> Self::Output {
                 self * <$type>::from(rhs)
             }
@@ -825,6 +1175,7 @@ macro_rules! impl_float_vec {
 
         impl Div<Float> for $type {
             type Output = Self;
+            #[track_caller]
             fn div(self, rhs: Float) -> Self::Output {
                 self / <$type>::from(rhs)
             }
@@ -832,6 +1183,7 @@ macro_rules! impl_float_vec {
 
         impl Rem<Float> for $type {
             type Output = Self;
+            #[track_caller]
             fn rem(self, rhs: Float) -> Self::Output {
                 self % <$type>::from(rhs)
             }
@@ -839,6 +1191,7 @@ macro_rules! impl_float_vec {
 
         impl Add<$type> for Float {
             type Output = $type;
+            #[track_caller]
             fn add(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) + rhs
             }
@@ -846,6 +1199,7 @@ macro_rules! impl_float_vec {
 
         impl Sub<$type> for Float {
             type Output = $type;
+            #[track_caller]
             fn sub(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) - rhs
             }
@@ -853,6 +1207,7 @@ macro_rules! impl_float_vec {
 
         impl Mul<$type> for Float {
             type Output = $type;
+            #[track_caller]
             fn mul(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) * rhs
             }
@@ -860,6 +1215,7 @@ macro_rules! impl_float_vec {
 
         impl Div<$type> for Float {
             type Output = $type;
+            #[track_caller]
             fn div(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) / rhs
             }
@@ -867,6 +1223,7 @@ macro_rules! impl_float_vec {
 
         impl Rem<$type> for Float {
             type Output = $type;
+            #[track_caller]
             fn rem(self, rhs: $type) -> Self::Output {
                 <$type>::from(self) % rhs
             }
@@ -879,6 +1236,7 @@ macro_rules! impl_loop_vars {
         impl<$($x: GlType),*> GlLoopVars for ($($x,)*) {
 
             #[allow(non_snake_case)]
+            #[track_caller]
             fn run_loop(self, condition: impl Fn(Self) -> Bool, body: impl FnOnce(Self) -> Self) -> Self {
                 let ($($x,)*) = self;
                 let ($($x,)*) = ($($x::wrap(push_op(Op::SlotCreate($x.unwrap()), $x::TYPE)),)*);
@@ -0,0 +1,79 @@
+use crate::{Float, Float2, Float4, GlFloat, Shader, ShaderContext, ShaderData};
+
+/// A linear gradient between two colors, going from `start` to `end` (in pixels).
+/// Pixels before `start` and after `end` get the edge colors. If `start` and `end` are the same point,
+/// everything gets `color_start`.
+#[derive(ShaderData, Clone, Copy, Debug)]
+pub struct LinearGradient {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    pub color_start: [f32; 4],
+    pub color_end: [f32; 4],
+}
+
+impl Shader for LinearGradient {
+    fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+        let start = Float2::new(shader.start[0], shader.start[1]);
+        let end = Float2::new(shader.end[0], shader.end[1]);
+        let color_start = Float4::new(
+            shader.color_start[0],
+            shader.color_start[1],
+            shader.color_start[2],
+            shader.color_start[3],
+        );
+        let color_end = Float4::new(
+            shader.color_end[0],
+            shader.color_end[1],
+            shader.color_end[2],
+            shader.color_end[3],
+        );
+
+        let direction = end - start;
+        // the projection is 0 for a zero direction, so this gives `color_start` instead of NaN
+        let length_sq = direction.dot(direction).max(1e-12);
+        let t = ((shader.position - start).dot(direction) / length_sq).saturate();
+
+        Float4::from(t).lerp(color_start, color_end)
+    }
+}
+
+/// A filled, anti-aliased rectangle with rounded corners.
+///
+/// The shape comes from `rect` rather than the quad bounds, as those are clipped to the render
+/// target and would move the corners of a partially visible rectangle. Draw it with bounds
+/// covering `rect`, everything outside of it is transparent.
+#[derive(ShaderData, Clone, Copy, Debug)]
+pub struct RoundedRect {
+    /// Left, top, right and bottom edge in pixels.
+    pub rect: [f32; 4],
+    pub radius: f32,
+    pub color: [f32; 4],
+}
+
+impl Shader for RoundedRect {
+    fn draw(shader: ShaderContext<Self::ShaderVars>) -> Float4 {
+        let [left, top, right, bottom] = shader.rect;
+        let center = Float2::new(left + right, top + bottom) * 0.5;
+        let half_size = Float2::new(right - left, bottom - top) * 0.5;
+
+        let dist = sdf_rounded_rect(shader.position - center, half_size, shader.radius);
+        let aa = dist.fwidth().max(0.001) * 0.5;
+        let mask = 1.0 - dist.smoothstep(-aa, aa);
+
+        Float4::new(
+            shader.color[0],
+            shader.color[1],
+            shader.color[2],
+            shader.color[3] * mask,
+        )
+    }
+}
+
+/// Signed distance from `position` (relative to the rectangle center) to a rounded rectangle.
+/// The radius is limited to half of the smaller side.
+#[track_caller]
+pub fn sdf_rounded_rect(position: Float2, half_size: Float2, radius: impl Into<Float>) -> Float {
+    let radius = radius.into().min(half_size.x().min(half_size.y()));
+    let q = position.abs() - half_size + radius;
+    q.max(0.0).len() + q.x().max(q.y()).min(0.0) - radius
+}